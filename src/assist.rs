@@ -0,0 +1,115 @@
+use crate::constants::{Overrides, Settings};
+use crate::pickup::PowerUps;
+use crate::system::{Boost, Input, Rot, RotVel, Time, Vel};
+use core::f32::consts::{PI, TAU};
+use macroquad::prelude::*;
+
+/// How much the ship helps its pilot. Runs after input has set `Boost` and `RotVel` and adjusts
+/// them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Assist {
+    /// raw controls
+    None,
+    /// when boosting up, rotation tends upward
+    AutoLevel,
+    /// levels out and boosts just enough to cancel gravity while up and down are released
+    HoverHold,
+    /// keeps whatever velocity the ship had when all keys were released
+    VelocityHold {
+        /// meters per second
+        held: Vec2,
+    },
+}
+
+/// How hard hover hold fights vertical velocity, per second.
+const HOVER_GAIN: f32 = 2.0;
+
+/// How hard velocity hold fights velocity error, per second.
+const VELOCITY_GAIN: f32 = 1.5;
+
+impl Assist {
+    pub fn next(&self) -> Assist {
+        match self {
+            Assist::None => Assist::AutoLevel,
+            Assist::AutoLevel => Assist::HoverHold,
+            Assist::HoverHold => Assist::VelocityHold {
+                held: vec2(0.0, 0.0),
+            },
+            Assist::VelocityHold { .. } => Assist::None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Assist::None => "none",
+            Assist::AutoLevel => "auto-level",
+            Assist::HoverHold => "hover-hold",
+            Assist::VelocityHold { .. } => "velocity-hold",
+        }
+    }
+
+    pub fn system(w: &mut hecs::World) {
+        let delta_t = Time::get(w).delta;
+        let global = Settings::get(w);
+
+        for (_id, (assist, input, boost, rv, r, v, overrides, power_ups)) in w.query_mut::<(
+            &mut Assist,
+            &Input,
+            &mut Boost,
+            &mut RotVel,
+            &Rot,
+            &Vel,
+            Option<&Overrides>,
+            Option<&PowerUps>,
+        )>() {
+            let settings = global.local(overrides, power_ups);
+            let rot = (r.0 + PI).rem_euclid(TAU) - PI;
+            debug_assert!(rot >= -PI - 0.0001);
+            debug_assert!(rot <= PI + 0.0001);
+            let thrusting = input.up || input.down;
+            let turning = input.left || input.right;
+
+            match assist {
+                Assist::None => {}
+                Assist::AutoLevel => {
+                    // when boosting up, rotation should tend upwards, it feels better that way
+                    if boost.0 >= 0.1 {
+                        rv.0 += -rot * settings.auto_up_power * delta_t;
+                    }
+                }
+                Assist::HoverHold => {
+                    if !turning {
+                        rv.0 += -rot * settings.auto_up_power * delta_t;
+                    }
+                    if !thrusting {
+                        let wanted = -settings.gravity - v.0.y * HOVER_GAIN;
+                        // only the vertical part of boost fights gravity
+                        let up = rot.cos();
+                        if up > 0.1 {
+                            boost.0 = (wanted / up).clamp(0.0, settings.boost_power);
+                        }
+                    }
+                }
+                Assist::VelocityHold { held } => {
+                    if thrusting || turning {
+                        *held = v.0;
+                        continue;
+                    }
+
+                    // drag will slow us down so push against it as well as the error
+                    let wanted = (*held - v.0) * VELOCITY_GAIN + v.0 * settings.drag_coefficient
+                        - vec2(0.0, settings.gravity);
+                    let heading = vec2(-rot.sin(), rot.cos());
+                    boost.0 = wanted.dot(heading).clamp(0.0, settings.boost_power);
+
+                    // turn toward the direction we need to push in, never faster than a pilot could
+                    let want = (-wanted.x).atan2(wanted.y);
+                    let error = (want - rot + PI).rem_euclid(TAU) - PI;
+                    let max_change = settings.rotational_acceleration * delta_t;
+                    let change = (error * 4.0 - rv.0).clamp(-max_change, max_change);
+                    rv.0 += change;
+                }
+            }
+        }
+    }
+}
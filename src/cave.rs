@@ -0,0 +1,148 @@
+use crate::commands::Commands;
+use crate::constants::color_pallet;
+use crate::fill::Fill;
+use crate::kinematic::Kinematic;
+use crate::system::{Collides, Pos, Rot, Vel, Wireframe};
+use crate::util::{value_noise, wireframe_to_polyline};
+use hecs::Entity;
+use macroquad::prelude::*;
+
+/// meters of tunnel per chunk
+const CHUNK_WIDTH: f32 = 200.0;
+
+/// meters between wall vertices
+const STEP: f32 = 10.0;
+
+/// meters of rock drawn behind each wall
+const ROCK_DEPTH: f32 = 400.0;
+
+/// how many chunks to keep generated in front of and behind the player
+const CHUNKS_AHEAD: i32 = 3;
+const CHUNKS_BEHIND: i32 = 2;
+
+/// world space points of one cave wall, left to right
+pub type WallLine = Vec<(f32, f32)>;
+
+/// Generates an endless tunnel around `follow`. The same seed always produces the same tunnel.
+pub struct Cave {
+    pub seed: u32,
+    pub follow: Entity,
+}
+
+/// Marks a piece of cave wall, the value is the chunk index.
+pub struct CaveChunk(pub i32);
+
+impl Cave {
+    pub fn system(w: &mut hecs::World) {
+        let mut wanted: Vec<(u32, i32)> = Vec::new();
+        for (_id, (cave,)) in w.query::<(&Cave,)>().iter() {
+            let x = match w.get::<Pos>(cave.follow) {
+                Ok(p) => p.0.x,
+                Err(_) => continue,
+            };
+            let current = (x / CHUNK_WIDTH).floor() as i32;
+            for chunk in (current - CHUNKS_BEHIND)..=(current + CHUNKS_AHEAD) {
+                wanted.push((cave.seed, chunk));
+            }
+        }
+
+        let mut existing: Vec<i32> = Vec::new();
+        let mut stale: Vec<Entity> = Vec::new();
+        for (ent, (chunk,)) in w.query_mut::<(&CaveChunk,)>() {
+            if wanted.iter().any(|(_, c)| *c == chunk.0) {
+                existing.push(chunk.0);
+            } else {
+                stale.push(ent);
+            }
+        }
+
+        let mut commands = Commands::default();
+        for ent in stale {
+            commands.despawn(ent);
+        }
+        commands.submit(w);
+
+        for (seed, chunk) in wanted {
+            if existing.contains(&chunk) {
+                continue;
+            }
+            existing.push(chunk);
+            let (ceiling, floor) = chunk_walls(seed, chunk);
+            spawn_wall(w, ceiling, 1.0, chunk);
+            spawn_wall(w, floor, -1.0, chunk);
+        }
+    }
+}
+
+/// `rock_side` is 1.0 when solid rock is above the wall, -1.0 when below.
+fn spawn_wall(w: &mut hecs::World, wall: WallLine, rock_side: f32, chunk: i32) {
+    // close the wall off into a slab of rock for filling
+    let (first, last) = (wall[0], wall[wall.len() - 1]);
+    let mut rock = wall.clone();
+    rock.push((last.0, last.1 + ROCK_DEPTH * rock_side));
+    rock.push((first.0, first.1 + ROCK_DEPTH * rock_side));
+    rock.push(first);
+    let fill = Fill::new(&rock, color_pallet()[8]).expect("cave rock never crosses itself");
+
+    w.spawn((
+        fill,
+        Collides(Box::new(wireframe_to_polyline(&wall))),
+        Wireframe(wall.into()),
+        Pos(vec2(0., 0.)),
+        Vel(vec2(0., 0.)),
+        Rot(0.),
+        Kinematic::still(vec2(0., 0.)),
+        color_pallet()[2],
+        CaveChunk(chunk),
+    ));
+}
+
+/// World space (ceiling, floor) polylines for a chunk. Neighboring chunks share end points.
+pub fn chunk_walls(seed: u32, chunk: i32) -> (WallLine, WallLine) {
+    let steps = (CHUNK_WIDTH / STEP) as i32;
+    let mut ceiling = Vec::new();
+    let mut floor = Vec::new();
+    for i in 0..=steps {
+        // integer step index keeps chunk edges bit-identical from both sides
+        let x = (chunk * steps + i) as f32 * STEP;
+        let (center, half_height) = tunnel_at(seed, x);
+        ceiling.push((x, center + half_height));
+        floor.push((x, center - half_height));
+    }
+    (ceiling, floor)
+}
+
+/// Points along the middle of the first few kilometers of tunnel.
+pub fn center_line(seed: u32) -> Vec<Vec2> {
+    (0..100)
+        .map(|i| {
+            let x = i as f32 * 50.0;
+            vec2(x, tunnel_at(seed, x).0)
+        })
+        .collect()
+}
+
+/// (center, half height) of the tunnel at x
+fn tunnel_at(seed: u32, x: f32) -> (f32, f32) {
+    // keep the start straight and roomy so players don't spawn inside rock
+    let calm = (x.abs() / 300.0).min(1.0);
+
+    let wander = value_noise(seed, x / 400.0, 0.0) - 0.5;
+    let wobble = value_noise(seed, x / 60.0, 10.0) - 0.5;
+    let center = (wander * 300.0 + wobble * 40.0) * calm;
+
+    let squeeze = value_noise(seed.wrapping_add(1), x / 250.0, 0.0);
+    let half_height = 120.0 - squeeze * 60.0 * calm;
+    (center, half_height)
+}
+
+#[test]
+fn same_seed_same_cave() {
+    for chunk in -3..3 {
+        assert_eq!(chunk_walls(1234, chunk), chunk_walls(1234, chunk));
+        let (_, floor) = chunk_walls(1234, chunk);
+        let (_, next_floor) = chunk_walls(1234, chunk + 1);
+        assert_eq!(floor.last(), next_floor.first());
+    }
+    assert_ne!(chunk_walls(1, 5), chunk_walls(2, 5));
+}
@@ -0,0 +1,89 @@
+use hecs::{Component, DynamicBundle, Entity};
+
+/// World changes queued by systems, applied at the end of each `schedule::Stage` so systems can
+/// queue them while iterating. Changes to entities that are gone by then are skipped.
+#[derive(Default)]
+pub struct Commands(Vec<Command>);
+
+type Command = Box<dyn FnOnce(&mut hecs::World) + Send + Sync>;
+
+/// commands queued by commands are applied in the same sync point, up to this many rounds
+const MAX_ROUNDS: usize = 8;
+
+impl Commands {
+    pub fn spawn(&mut self, bundle: impl DynamicBundle + Send + Sync + 'static) {
+        self.0.push(Box::new(move |w| {
+            w.spawn(bundle);
+        }));
+    }
+
+    pub fn despawn(&mut self, ent: Entity) {
+        self.0.push(Box::new(move |w| {
+            let _ = w.despawn(ent);
+        }));
+    }
+
+    pub fn insert(&mut self, ent: Entity, bundle: impl DynamicBundle + Send + Sync + 'static) {
+        self.0.push(Box::new(move |w| {
+            let _ = w.insert(ent, bundle);
+        }));
+    }
+
+    pub fn remove<T: Component>(&mut self, ent: Entity) {
+        self.0.push(Box::new(move |w| {
+            let _ = w.remove_one::<T>(ent);
+        }));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Hands queued commands to the world's buffer.
+    pub fn submit(self, w: &mut hecs::World) {
+        if self.is_empty() {
+            return;
+        }
+        let mut commands = Some(self);
+        if let Some((_, (c,))) = w.query_mut::<(&mut Commands,)>().into_iter().next() {
+            c.0.extend(commands.take().unwrap().0);
+        }
+        if let Some(commands) = commands {
+            w.spawn((commands,));
+        }
+    }
+
+    /// A sync point, runs everything in the world's buffer.
+    pub fn apply(w: &mut hecs::World) {
+        for _ in 0..MAX_ROUNDS {
+            let queued: Vec<Command> = w
+                .query_mut::<(&mut Commands,)>()
+                .into_iter()
+                .flat_map(|(_, (c,))| core::mem::take(&mut c.0))
+                .collect();
+            if queued.is_empty() {
+                return;
+            }
+            for command in queued {
+                command(w);
+            }
+        }
+    }
+}
+
+#[test]
+fn despawn_twice_is_fine() {
+    let mut w = hecs::World::new();
+    let ent = w.spawn((1u32,));
+    let mut commands = Commands::default();
+    commands.despawn(ent);
+    commands.despawn(ent);
+    commands.insert(ent, (2u32,));
+    commands.spawn((3u32,));
+    commands.submit(&mut w);
+    Commands::apply(&mut w);
+
+    assert!(!w.contains(ent));
+    let left: Vec<u32> = w.query::<(&u32,)>().iter().map(|(_, (n,))| *n).collect();
+    assert_eq!(left, vec![3]);
+}
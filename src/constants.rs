@@ -1,3 +1,4 @@
+use crate::pickup::PowerUps;
 use crate::util::color;
 use macroquad::color::Color;
 use macroquad::ui::{hash, Ui};
@@ -18,8 +19,39 @@ pub struct Settings {
     pub auto_up_power: f32,
     /// what percentage of velocity is lost on collision
     pub collision_energy_loss: f32,
+    /// multiplies the size of every ship
+    pub ship_size: f32,
+    /// multiplies how hard exhaust particles push what they fly into, 0 turns downwash off
+    pub downwash: f32,
 }
 
+/// Per-entity changes to the global `Settings`, for heavy cargo, floaty debris or ships that
+/// handle differently. Fields left `None` use the global value.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Overrides {
+    /// multiplies gravity, 0 floats and below 0 falls up
+    pub gravity_scale: Option<f32>,
+    pub drag_coefficient: Option<f32>,
+    /// meters per second per second
+    pub boost_power: Option<f32>,
+    /// radians per second per second
+    pub rotational_acceleration: Option<f32>,
+    pub rotational_drag_coefficient: Option<f32>,
+    /// a ship weighs 1, heavier bodies give less in collisions and are pushed less by recoil
+    /// and downwash
+    pub mass: Option<f32>,
+}
+
+/// every field of `Overrides`, in declaration order
+pub const OVERRIDE_NAMES: [&str; 6] = [
+    "gravity_scale",
+    "drag_coefficient",
+    "boost_power",
+    "rotational_acceleration",
+    "rotational_drag_coefficient",
+    "mass",
+];
+
 impl Default for Settings {
     fn default() -> Self {
         Self {
@@ -30,11 +62,54 @@ impl Default for Settings {
             boost_power: 30.0,
             auto_up_power: 2.0,
             collision_energy_loss: 0.1,
+            ship_size: 1.0,
+            downwash: 0.0,
         }
     }
 }
 
 impl Settings {
+    /// panics if there is more than one entity with Settings
+    pub fn get(w: &hecs::World) -> Settings {
+        let count = w.query::<(&Settings,)>().iter().count();
+        assert!(count <= 1);
+        w.query::<(&Settings,)>()
+            .iter()
+            .next()
+            .map(|(_, (s,))| s.clone())
+            .unwrap_or_default()
+    }
+
+    /// Sets a field by name, false if there is no such field.
+    pub fn set(&mut self, name: &str, value: f32) -> bool {
+        let field = match name {
+            "gravity" => &mut self.gravity,
+            "rotational_acceleration" => &mut self.rotational_acceleration,
+            "rotational_drag_coefficient" => &mut self.rotational_drag_coefficient,
+            "drag_coefficient" => &mut self.drag_coefficient,
+            "boost_power" => &mut self.boost_power,
+            "auto_up_power" => &mut self.auto_up_power,
+            "collision_energy_loss" => &mut self.collision_energy_loss,
+            "ship_size" => &mut self.ship_size,
+            "downwash" => &mut self.downwash,
+            _ => return false,
+        };
+        *field = value;
+        true
+    }
+
+    /// What one entity feels: its `Overrides`, then its power-ups, on top of these.
+    pub fn local(&self, overrides: Option<&Overrides>, power_ups: Option<&PowerUps>) -> Settings {
+        let ret = match overrides {
+            Some(o) => o.apply(self),
+            None => self.clone(),
+        };
+        match power_ups {
+            Some(p) => p.apply(&ret),
+            None => ret,
+        }
+    }
+
     pub fn ui(&mut self, ui: &mut Ui) {
         let def = Self::default();
         let range = |radius, default| (default - radius)..(default + radius);
@@ -83,12 +158,69 @@ impl Settings {
             range(def.collision_energy_loss, def.collision_energy_loss),
             &mut self.collision_energy_loss,
         );
+        ui.slider(
+            hash!(),
+            "ship_size",
+            range(def.ship_size * 0.8, def.ship_size),
+            &mut self.ship_size,
+        );
+        ui.slider(hash!(), "downwash", 0.0..4.0, &mut self.downwash);
     }
 }
 
+impl Overrides {
+    pub fn apply(&self, settings: &Settings) -> Settings {
+        let mut ret = settings.clone();
+        ret.gravity *= self.gravity_scale.unwrap_or(1.0);
+        ret.drag_coefficient = self.drag_coefficient.unwrap_or(ret.drag_coefficient);
+        ret.boost_power = self.boost_power.unwrap_or(ret.boost_power);
+        ret.rotational_acceleration = self
+            .rotational_acceleration
+            .unwrap_or(ret.rotational_acceleration);
+        ret.rotational_drag_coefficient = self
+            .rotational_drag_coefficient
+            .unwrap_or(ret.rotational_drag_coefficient);
+        ret
+    }
+
+    pub fn mass(&self) -> f32 {
+        self.mass.unwrap_or(1.0)
+    }
+
+    /// A field by name, `None` if there is no such field.
+    pub fn field(&mut self, name: &str) -> Option<&mut Option<f32>> {
+        Some(match name {
+            "gravity_scale" => &mut self.gravity_scale,
+            "drag_coefficient" => &mut self.drag_coefficient,
+            "boost_power" => &mut self.boost_power,
+            "rotational_acceleration" => &mut self.rotational_acceleration,
+            "rotational_drag_coefficient" => &mut self.rotational_drag_coefficient,
+            "mass" => &mut self.mass,
+            _ => return None,
+        })
+    }
+
+    /// Whether the field called `name` can be set to `value`, loaders reject values it can't.
+    /// Masses must be positive, collisions divide by them.
+    pub fn allows(name: &str, value: f32) -> bool {
+        name != "mass" || value > 0.0
+    }
+}
+
+/// The mass of `ent`, 1 unless overridden.
+pub fn mass_of(w: &hecs::World, ent: hecs::Entity) -> f32 {
+    w.get::<Overrides>(ent).map_or(1.0, |o| o.mass())
+}
+
 /// meters width, height
 pub const PLAYER_SIZE: f32 = 10.0;
 
+/// hit points every ship starts with
+pub const SHIP_HEALTH: f32 = 100.0;
+
+/// seconds of boosting a full tank allows
+pub const SHIP_FUEL: f32 = 20.0;
+
 /// meters
 pub const WORLD_HEIGHT: f32 = 500.0;
 
@@ -101,6 +233,34 @@ pub const PLAYER_WIREFRAME: &[(f32, f32)] = &[
     (-0.5 * PLAYER_SIZE, -0.5 * PLAYER_SIZE),
 ];
 
+/// wide and blunt, for the heavy class
+pub const HEAVY_WIREFRAME: &[(f32, f32)] = &[
+    (-0.7 * PLAYER_SIZE, -0.5 * PLAYER_SIZE),
+    (-0.3 * PLAYER_SIZE, 0.5 * PLAYER_SIZE),
+    (0.3 * PLAYER_SIZE, 0.5 * PLAYER_SIZE),
+    (0.7 * PLAYER_SIZE, -0.5 * PLAYER_SIZE),
+    (0.0 * PLAYER_SIZE, -0.1 * PLAYER_SIZE),
+    (-0.7 * PLAYER_SIZE, -0.5 * PLAYER_SIZE),
+];
+
+/// `HEAVY_WIREFRAME` without the notch, so nothing catches underneath
+pub const HEAVY_HULL: &[(f32, f32)] = &[
+    (-0.7 * PLAYER_SIZE, -0.5 * PLAYER_SIZE),
+    (-0.3 * PLAYER_SIZE, 0.5 * PLAYER_SIZE),
+    (0.3 * PLAYER_SIZE, 0.5 * PLAYER_SIZE),
+    (0.7 * PLAYER_SIZE, -0.5 * PLAYER_SIZE),
+    (-0.7 * PLAYER_SIZE, -0.5 * PLAYER_SIZE),
+];
+
+/// narrow and pointed, for the dart class
+pub const DART_WIREFRAME: &[(f32, f32)] = &[
+    (-0.35 * PLAYER_SIZE, -0.5 * PLAYER_SIZE),
+    (0.0 * PLAYER_SIZE, 0.6 * PLAYER_SIZE),
+    (0.35 * PLAYER_SIZE, -0.5 * PLAYER_SIZE),
+    (0.0 * PLAYER_SIZE, -0.2 * PLAYER_SIZE),
+    (-0.35 * PLAYER_SIZE, -0.5 * PLAYER_SIZE),
+];
+
 pub const BOUNDS_WIREFRAME: &[(f32, f32)] = &[
     (-1. * WORLD_HEIGHT / 2., -1. * WORLD_HEIGHT / 2.),
     (-1. * WORLD_HEIGHT / 2., 1.0 * WORLD_HEIGHT / 2.),
@@ -117,6 +277,43 @@ pub const TRASH_WIREFRAME: &[(f32, f32)] = &[
     (-5., -5.),
 ];
 
+pub const SPARK_WIREFRAME: &[(f32, f32)] = &[(0.0, -1.5), (0.0, 1.5)];
+
+pub const PLATFORM_WIREFRAME: &[(f32, f32)] = &[
+    (-30., -3.),
+    (-30., 3.0),
+    (30.0, 3.0),
+    (30.0, -3.),
+    (-30., -3.),
+];
+
+/// a row of teeth standing on y = 0
+pub const SPIKES_WIREFRAME: &[(f32, f32)] = &[
+    (-30., 0.0),
+    (-25., 8.0),
+    (-20., 0.0),
+    (-15., 8.0),
+    (-10., 0.0),
+    (-5.0, 8.0),
+    (0.0, 0.0),
+    (5.0, 8.0),
+    (10.0, 0.0),
+    (15.0, 8.0),
+    (20.0, 0.0),
+    (25.0, 8.0),
+    (30.0, 0.0),
+    (-30., 0.0),
+];
+
+/// a diamond around the origin
+pub const PICKUP_WIREFRAME: &[(f32, f32)] = &[
+    (0.0, -5.0),
+    (-5.0, 0.0),
+    (0.0, 5.0),
+    (5.0, 0.0),
+    (0.0, -5.0),
+];
+
 pub fn color_pallet() -> [Color; 10] {
     [
         color(0x58, 0x2f, 0x0e),
@@ -137,3 +334,23 @@ fn cel() {
     assert!(Settings::default().collision_energy_loss >= 0.0);
     assert!(Settings::default().collision_energy_loss <= 1.0);
 }
+
+#[test]
+fn overrides_then_power_ups() {
+    use crate::pickup::PickupKind;
+
+    let global = Settings::default();
+    let heavy = Overrides {
+        gravity_scale: Some(2.0),
+        boost_power: Some(50.0),
+        ..Overrides::default()
+    };
+    let local = global.local(Some(&heavy), None);
+    assert_eq!(local.gravity, global.gravity * 2.0);
+    assert_eq!(local.boost_power, 50.0);
+    assert_eq!(local.drag_coefficient, global.drag_coefficient);
+
+    let flipped = PowerUps(vec![(PickupKind::Gravity, 1.0)]);
+    let local = global.local(Some(&heavy), Some(&flipped));
+    assert_eq!(local.gravity, -global.gravity * 2.0);
+}
@@ -0,0 +1,131 @@
+use crate::constants::WORLD_HEIGHT;
+use crate::system::{Collides, Contacts, Pos, Rot, RotVel, Vel};
+use macroquad::prelude::*;
+use parry2d::math::Isometry;
+use parry2d::shape::Polyline;
+
+/// While this exists the physics overlay is drawn. Toggled with F1.
+#[derive(Debug, Clone, Copy)]
+pub struct DebugOverlay;
+
+/// seconds of travel shown by velocity arrows
+const VELOCITY_SCALE: f32 = 0.25;
+
+/// meters
+const HEADING_LENGTH: f32 = 15.0;
+
+/// max rows in the entity panel
+const PANEL_ROWS: usize = 30;
+
+impl DebugOverlay {
+    pub fn enabled(w: &hecs::World) -> bool {
+        w.query::<(&DebugOverlay,)>().iter().next().is_some()
+    }
+
+    pub fn toggle(w: &mut hecs::World) {
+        let existing: Vec<hecs::Entity> = w
+            .query::<(&DebugOverlay,)>()
+            .iter()
+            .map(|(ent, _)| ent)
+            .collect();
+        if existing.is_empty() {
+            w.spawn((DebugOverlay,));
+        }
+        for ent in existing {
+            w.despawn(ent).unwrap();
+        }
+    }
+
+    /// Shapes, bounding boxes, contacts and motion. Expects the world camera to be set.
+    pub fn draw_world(w: &hecs::World) {
+        let shape_color = Color::new(0.0, 1.0, 0.0, 0.8);
+        let aabb_color = Color::new(0.0, 0.6, 1.0, 0.6);
+        let contact_color = Color::new(1.0, 0.0, 0.0, 1.0);
+        let velocity_color = Color::new(1.0, 1.0, 0.0, 0.8);
+        let heading_color = Color::new(1.0, 0.0, 1.0, 0.8);
+
+        for (_, (collides, pos, rot)) in w.query::<(&Collides, &Pos, &Rot)>().iter() {
+            let iso = Isometry::new([pos.0.x, pos.0.y].into(), rot.0);
+
+            if let Some(polyline) = collides.0.as_shape::<Polyline>() {
+                for segment in polyline.segments() {
+                    let a = iso * segment.a;
+                    let b = iso * segment.b;
+                    line(vec2(a.x, a.y), vec2(b.x, b.y), 0.5, shape_color);
+                }
+            }
+
+            let aabb = collides.0.compute_aabb(&iso);
+            let (min, max) = (
+                vec2(aabb.mins.x, aabb.mins.y),
+                vec2(aabb.maxs.x, aabb.maxs.y),
+            );
+            line(min, vec2(max.x, min.y), 0.3, aabb_color);
+            line(vec2(max.x, min.y), max, 0.3, aabb_color);
+            line(max, vec2(min.x, max.y), 0.3, aabb_color);
+            line(vec2(min.x, max.y), min, 0.3, aabb_color);
+        }
+
+        for (_, (contacts,)) in w.query::<(&Contacts,)>().iter() {
+            for contact in &contacts.0 {
+                dot(contact.point, 1.5, contact_color);
+                line(
+                    contact.point,
+                    contact.point + contact.normal * 10.0,
+                    0.5,
+                    contact_color,
+                );
+            }
+        }
+
+        for (_, (pos, vel)) in w.query::<(&Pos, &Vel)>().iter() {
+            if vel.0.length() > 0.001 {
+                line(pos.0, pos.0 + vel.0 * VELOCITY_SCALE, 0.5, velocity_color);
+            }
+        }
+
+        for (_, (pos, rot, _)) in w.query::<(&Pos, &Rot, &Collides)>().iter() {
+            let heading = vec2(-rot.0.sin(), rot.0.cos());
+            line(pos.0, pos.0 + heading * HEADING_LENGTH, 0.5, heading_color);
+        }
+    }
+
+    /// Per entity numbers. Expects the default camera to be set.
+    pub fn draw_panel(w: &hecs::World) {
+        let font_size = 16.0;
+        let x = screen_width() - 420.0;
+        let mut y = 20.0;
+        draw_text("entity     pos              vel              rotvel", x, y, font_size, WHITE);
+
+        for (ent, (pos, vel, rv)) in w
+            .query::<(&Pos, &Vel, Option<&RotVel>)>()
+            .iter()
+            .take(PANEL_ROWS)
+        {
+            y += font_size;
+            let rv = rv.map(|rv| rv.0).unwrap_or(0.0);
+            let row = format!(
+                "{:<10} {:>7.1},{:>7.1} {:>7.1},{:>7.1} {:>6.2}",
+                ent.id(),
+                pos.0.x,
+                pos.0.y,
+                vel.0.x,
+                vel.0.y,
+                rv
+            );
+            draw_text(&row, x, y, font_size, WHITE);
+        }
+    }
+}
+
+fn line(a: Vec2, b: Vec2, width_meters: f32, color: Color) {
+    let meters_per_screen = 1.0 / WORLD_HEIGHT;
+    let (a, b) = (a * meters_per_screen, b * meters_per_screen);
+    draw_line(a.x, a.y, b.x, b.y, width_meters * meters_per_screen, color);
+}
+
+fn dot(p: Vec2, radius_meters: f32, color: Color) {
+    let meters_per_screen = 1.0 / WORLD_HEIGHT;
+    let p = p * meters_per_screen;
+    draw_circle(p.x, p.y, radius_meters * meters_per_screen, color);
+}
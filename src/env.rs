@@ -0,0 +1,294 @@
+//! Gym style training environment over the headless simulation. Nothing here opens a window,
+//! reads the keyboard or looks at the wall clock, so many environments can run side by side.
+
+use crate::constants::{Settings, WORLD_HEIGHT};
+use crate::level::Level;
+use crate::particles::Emitter;
+use crate::system::{spawn_ship, Heli, Input, Pos, Rot, RotVel, Time, Vel};
+use crate::util::Rng;
+use hecs::Entity;
+use macroquad::prelude::*;
+
+/// Buttons held for one step.
+pub type Action = Input;
+
+/// What the agent gets to see after each step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Observation {
+    pub pos: Vec2,
+    pub vel: Vec2,
+    pub rot: f32,
+    pub rot_vel: f32,
+    /// where the task wants the ship to go
+    pub target: Vec2,
+}
+
+impl Observation {
+    /// Flattened for feeding to a network.
+    pub fn to_array(&self) -> [f32; 8] {
+        [
+            self.pos.x,
+            self.pos.y,
+            self.vel.x,
+            self.vel.y,
+            self.rot,
+            self.rot_vel,
+            self.target.x,
+            self.target.y,
+        ]
+    }
+}
+
+/// Decides what the agent is rewarded for.
+pub trait Task: Send {
+    /// Start of an episode, pick targets.
+    fn reset(&mut self, rng: &mut Rng);
+
+    fn target(&self) -> Vec2;
+
+    /// (reward, done) for the state after a step
+    fn reward(&mut self, obs: &Observation) -> (f32, bool);
+}
+
+/// Reward for staying still near a point.
+#[derive(Debug, Clone)]
+pub struct HoverAt {
+    /// None picks a random point each episode
+    pub target: Option<Vec2>,
+    current: Vec2,
+}
+
+impl HoverAt {
+    pub fn new(target: Option<Vec2>) -> Self {
+        Self {
+            target,
+            current: target.unwrap_or(vec2(0.0, 0.0)),
+        }
+    }
+}
+
+impl Task for HoverAt {
+    fn reset(&mut self, rng: &mut Rng) {
+        self.current = self.target.unwrap_or_else(|| random_point(rng));
+    }
+
+    fn target(&self) -> Vec2 {
+        self.current
+    }
+
+    fn reward(&mut self, obs: &Observation) -> (f32, bool) {
+        let distance = (obs.pos - self.current).length();
+        let reward = -distance / WORLD_HEIGHT - obs.vel.length() * 0.001;
+        (reward, false)
+    }
+}
+
+/// Reward for flying through checkpoints in order. The episode ends after the last one.
+#[derive(Debug, Clone)]
+pub struct ReachCheckpoint {
+    /// None picks `count` random checkpoints each episode
+    pub checkpoints: Option<Vec<Vec2>>,
+    pub count: usize,
+    /// meters
+    pub radius: f32,
+    current: Vec<Vec2>,
+    next: usize,
+}
+
+impl ReachCheckpoint {
+    pub fn new(checkpoints: Option<Vec<Vec2>>) -> Self {
+        Self {
+            count: checkpoints.as_ref().map(|c| c.len()).unwrap_or(3),
+            current: checkpoints.clone().unwrap_or_default(),
+            checkpoints,
+            radius: 15.0,
+            next: 0,
+        }
+    }
+}
+
+impl Task for ReachCheckpoint {
+    fn reset(&mut self, rng: &mut Rng) {
+        self.next = 0;
+        self.current = match &self.checkpoints {
+            Some(checkpoints) => checkpoints.clone(),
+            None => (0..self.count).map(|_| random_point(rng)).collect(),
+        };
+    }
+
+    fn target(&self) -> Vec2 {
+        self.current
+            .get(self.next)
+            .or_else(|| self.current.last())
+            .cloned()
+            .unwrap_or(vec2(0.0, 0.0))
+    }
+
+    fn reward(&mut self, obs: &Observation) -> (f32, bool) {
+        let target = match self.current.get(self.next) {
+            Some(target) => *target,
+            None => return (0.0, true),
+        };
+        let distance = (obs.pos - target).length();
+        if distance < self.radius {
+            self.next += 1;
+            return (1.0, self.next >= self.current.len());
+        }
+        // small shaping term so the agent finds the first checkpoint at all
+        (-distance / WORLD_HEIGHT * 0.01, false)
+    }
+}
+
+/// A single ship alone in the walled arena.
+pub struct Env {
+    heli: Heli,
+    ship: Entity,
+    task: Box<dyn Task>,
+    rng: Rng,
+    steps: u32,
+    /// episodes end after this many steps even if the task isn't done
+    pub max_steps: u32,
+    /// seconds per step
+    pub delta_t: f32,
+    /// physics used from the next reset on
+    pub settings: Settings,
+}
+
+impl Env {
+    /// Starts in the state `reset(0)` leaves behind.
+    pub fn new(mut task: Box<dyn Task>) -> Self {
+        let settings = Settings::default();
+        let mut rng = Rng::new(0);
+        task.reset(&mut rng);
+        let (heli, ship) = build_world(&settings, &mut rng);
+        Self {
+            heli,
+            ship,
+            task,
+            rng,
+            steps: 0,
+            max_steps: 60 * 20,
+            delta_t: 1.0 / 60.0,
+            settings,
+        }
+    }
+
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.rng = Rng::new(seed);
+        self.steps = 0;
+        self.task.reset(&mut self.rng);
+        let (heli, ship) = build_world(&self.settings, &mut self.rng);
+        self.heli = heli;
+        self.ship = ship;
+        self.observe()
+    }
+
+    /// (observation, reward, done)
+    pub fn step(&mut self, action: Action) -> (Observation, f32, bool) {
+        *self
+            .heli
+            .world_mut()
+            .get_mut::<Input>(self.ship)
+            .unwrap() = action;
+        self.heli.step(self.delta_t);
+        self.steps += 1;
+
+        let obs = self.observe();
+        let (reward, done) = self.task.reward(&obs);
+        (obs, reward, done || self.steps >= self.max_steps)
+    }
+
+    fn observe(&self) -> Observation {
+        let w = self.heli.world();
+        Observation {
+            pos: w.get::<Pos>(self.ship).unwrap().0,
+            vel: w.get::<Vel>(self.ship).unwrap().0,
+            rot: w.get::<Rot>(self.ship).unwrap().0,
+            rot_vel: w.get::<RotVel>(self.ship).unwrap().0,
+            target: self.task.target(),
+        }
+    }
+}
+
+/// Many independent environments stepped together. Finished environments start a new episode
+/// right away, the observation returned for them is the first of the new episode.
+pub struct VecEnv {
+    pub envs: Vec<Env>,
+}
+
+impl VecEnv {
+    pub fn new(count: usize, task: impl Fn() -> Box<dyn Task>) -> Self {
+        Self {
+            envs: (0..count).map(|_| Env::new(task())).collect(),
+        }
+    }
+
+    /// Environment `i` is seeded with `seed + i`.
+    pub fn reset(&mut self, seed: u64) -> Vec<Observation> {
+        self.envs
+            .iter_mut()
+            .enumerate()
+            .map(|(i, env)| env.reset(seed.wrapping_add(i as u64)))
+            .collect()
+    }
+
+    pub fn step(&mut self, actions: &[Action]) -> Vec<(Observation, f32, bool)> {
+        assert_eq!(actions.len(), self.envs.len());
+        self.envs
+            .iter_mut()
+            .zip(actions)
+            .map(|(env, action)| {
+                let (mut obs, reward, done) = env.step(*action);
+                if done {
+                    let seed = env.rng.next_u64();
+                    obs = env.reset(seed);
+                }
+                (obs, reward, done)
+            })
+            .collect()
+    }
+}
+
+fn build_world(settings: &Settings, rng: &mut Rng) -> (Heli, Entity) {
+    let mut world = hecs::World::new();
+    Level::empty().spawn(&mut world).unwrap();
+    world.spawn((settings.clone(),));
+    world.spawn((Time::default(),));
+    let ship = spawn_ship(&mut world, random_point(rng), BLACK);
+    // exhaust particles are only for looks
+    world.remove_one::<Emitter>(ship).unwrap();
+    (Heli::from_world(world), ship)
+}
+
+/// somewhere comfortably inside the arena walls
+fn random_point(rng: &mut Rng) -> Vec2 {
+    let r = WORLD_HEIGHT * 0.35;
+    vec2(rng.range(-r, r), rng.range(-r, r))
+}
+
+#[test]
+fn same_seed_same_episode() {
+    let actions: Vec<Action> = (0..200)
+        .map(|i| Input {
+            up: i % 3 == 0,
+            left: i % 7 < 3,
+            ..Default::default()
+        })
+        .collect();
+
+    let run = |seed| {
+        let mut env = Env::new(Box::new(HoverAt::new(None)));
+        let mut trace = vec![env.reset(seed)];
+        for action in &actions {
+            trace.push(env.step(*action).0);
+        }
+        trace
+    };
+
+    assert_eq!(run(3), run(3));
+    assert_ne!(run(3), run(4));
+
+    let mut env = Env::new(Box::new(HoverAt::new(None)));
+    let fresh = (env.observe(), env.task.target());
+    assert_eq!((env.reset(0), env.task.target()), fresh);
+}
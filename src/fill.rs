@@ -0,0 +1,187 @@
+use crate::constants::WORLD_HEIGHT;
+use core::fmt;
+use macroquad::prelude::*;
+
+/// Draws the inside of a closed `Wireframe`. The outline is still drawn on top in the entity's
+/// `Color`.
+#[derive(Debug, Clone)]
+pub struct Fill {
+    pub color: Color,
+    /// local space, unscaled
+    triangles: Vec<[Vec2; 3]>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WireframeError {
+    /// the last point must repeat the first
+    NotClosed,
+    /// fewer than three distinct corners
+    TooFewPoints,
+    /// indices of two segments that cross
+    SelfIntersecting(usize, usize),
+}
+
+impl fmt::Display for WireframeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WireframeError::NotClosed => write!(f, "wireframe is not closed"),
+            WireframeError::TooFewPoints => write!(f, "wireframe has fewer than three corners"),
+            WireframeError::SelfIntersecting(a, b) => {
+                write!(f, "wireframe segments {} and {} cross", a, b)
+            }
+        }
+    }
+}
+
+impl Fill {
+    /// Triangulates the wireframe up front, fails if it can't be filled.
+    pub fn new(wireframe: &[(f32, f32)], color: Color) -> Result<Self, WireframeError> {
+        let polygon = closed_polygon(wireframe)?;
+        Ok(Self {
+            color,
+            triangles: triangulate(&polygon),
+        })
+    }
+
+    /// Expects the world camera to be set.
+    pub fn draw(&self, position: Vec2, rotation: Quat, scale: Vec2, alpha: f32) {
+        let meters_per_screen = 1.0 / WORLD_HEIGHT;
+        let to_screen = |p: Vec2| {
+            let p = rotation.mul_vec3((p * scale).extend(0.0)).truncate();
+            (p + position) * meters_per_screen
+        };
+        let color = Color {
+            a: self.color.a * alpha,
+            ..self.color
+        };
+        for [a, b, c] in &self.triangles {
+            draw_triangle(to_screen(*a), to_screen(*b), to_screen(*c), color);
+        }
+    }
+}
+
+/// The corners of a closed, simple wireframe, without the repeated last point.
+pub fn closed_polygon(wireframe: &[(f32, f32)]) -> Result<Vec<Vec2>, WireframeError> {
+    if wireframe.len() < 2 || wireframe.first() != wireframe.last() {
+        return Err(WireframeError::NotClosed);
+    }
+    let polygon: Vec<Vec2> = wireframe[..wireframe.len() - 1]
+        .iter()
+        .map(|(x, y)| vec2(*x, *y))
+        .collect();
+    if polygon.len() < 3 {
+        return Err(WireframeError::TooFewPoints);
+    }
+
+    let n = polygon.len();
+    let segment = |i: usize| (polygon[i], polygon[(i + 1) % n]);
+    for i in 0..n {
+        for j in (i + 1)..n {
+            let adjacent = j == i + 1 || (i == 0 && j == n - 1);
+            if adjacent {
+                continue;
+            }
+            let (a, b) = segment(i);
+            let (c, d) = segment(j);
+            if segments_cross(a, b, c, d) {
+                return Err(WireframeError::SelfIntersecting(i, j));
+            }
+        }
+    }
+    Ok(polygon)
+}
+
+/// Ear clipping. Expects a simple polygon in either winding order.
+pub fn triangulate(polygon: &[Vec2]) -> Vec<[Vec2; 3]> {
+    let mut indices: Vec<usize> = (0..polygon.len()).collect();
+    if signed_area(polygon) < 0.0 {
+        indices.reverse();
+    }
+
+    let mut ret = Vec::new();
+    while indices.len() > 3 {
+        let n = indices.len();
+        let ear = (0..n).find(|&i| {
+            let (a, b, c) = (
+                polygon[indices[(i + n - 1) % n]],
+                polygon[indices[i]],
+                polygon[indices[(i + 1) % n]],
+            );
+            let convex = (b - a).perp_dot(c - b) > 0.0;
+            convex
+                && indices
+                    .iter()
+                    .map(|&k| polygon[k])
+                    .filter(|p| *p != a && *p != b && *p != c)
+                    .all(|p| !in_triangle(p, a, b, c))
+        });
+        let i = match ear {
+            Some(i) => i,
+            // only degenerate (collinear) polygons get here, nothing left worth drawing
+            None => return ret,
+        };
+        ret.push([
+            polygon[indices[(i + n - 1) % n]],
+            polygon[indices[i]],
+            polygon[indices[(i + 1) % n]],
+        ]);
+        indices.remove(i);
+    }
+    if indices.len() == 3 {
+        ret.push([
+            polygon[indices[0]],
+            polygon[indices[1]],
+            polygon[indices[2]],
+        ]);
+    }
+    ret
+}
+
+fn signed_area(polygon: &[Vec2]) -> f32 {
+    let mut twice_area = 0.0;
+    for (a, b) in polygon.iter().zip(polygon.iter().cycle().skip(1)) {
+        twice_area += a.perp_dot(*b);
+    }
+    twice_area / 2.0
+}
+
+fn in_triangle(p: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    let d1 = (b - a).perp_dot(p - a);
+    let d2 = (c - b).perp_dot(p - b);
+    let d3 = (a - c).perp_dot(p - c);
+    d1 >= 0.0 && d2 >= 0.0 && d3 >= 0.0
+}
+
+fn segments_cross(a: Vec2, b: Vec2, c: Vec2, d: Vec2) -> bool {
+    let side = |p: Vec2, q: Vec2, r: Vec2| (q - p).perp_dot(r - p);
+    let (d1, d2) = (side(c, d, a), side(c, d, b));
+    let (d3, d4) = (side(a, b, c), side(a, b, d));
+    (d1 > 0.0) != (d2 > 0.0) && (d3 > 0.0) != (d4 > 0.0) && d1 != 0.0 && d3 != 0.0
+}
+
+#[test]
+fn fill_concave_and_reject_bad_wireframes() {
+    // an L shape, area 3
+    let l = &[
+        (0.0, 0.0),
+        (2.0, 0.0),
+        (2.0, 1.0),
+        (1.0, 1.0),
+        (1.0, 2.0),
+        (0.0, 2.0),
+        (0.0, 0.0),
+    ];
+    let triangles = triangulate(&closed_polygon(l).unwrap());
+    assert_eq!(triangles.len(), 4);
+    let area: f32 = triangles.iter().map(|[a, b, c]| signed_area(&[*a, *b, *c])).sum();
+    assert!((area - 3.0).abs() < 0.0001);
+
+    let open = &[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)];
+    assert_eq!(closed_polygon(open), Err(WireframeError::NotClosed));
+
+    let bowtie = &[(0.0, 0.0), (1.0, 1.0), (1.0, 0.0), (0.0, 1.0), (0.0, 0.0)];
+    assert_eq!(
+        closed_polygon(bowtie),
+        Err(WireframeError::SelfIntersecting(0, 2))
+    );
+}
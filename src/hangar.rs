@@ -0,0 +1,250 @@
+//! Ship classes. Each class has its own shape, handling, fuel, health and gun, and players
+//! pick one before a match. Classes are balanced by their values here rather than special cases
+//! in the systems: handling goes through `Overrides` like any other entity's.
+
+use crate::constants::*;
+use crate::pickup::Fuel;
+use crate::scale::Scale;
+use crate::snapshot::{self, DataError, DataFile};
+use crate::spawner::Spawner;
+use crate::system::{Collides, Hull, Wireframe};
+use crate::util::wireframe_to_polyline;
+use crate::weapon::{Health, Weapon};
+use hecs::Entity;
+
+pub const HANGAR_HEADER: &str = "heli ships 1";
+
+#[derive(Debug, Clone)]
+pub struct ShipClass {
+    pub name: String,
+    pub wireframe: &'static [(f32, f32)],
+    /// collision outline, the wireframe if `None`
+    pub hull: Option<&'static [(f32, f32)]>,
+    /// handling, fields left `None` follow the global `Settings`
+    pub overrides: Overrides,
+    /// seconds of boost in a full tank
+    pub fuel: f32,
+    pub health: f32,
+    pub weapon: Weapon,
+}
+
+/// Every class players can pick from, in menu order.
+#[derive(Debug, Clone)]
+pub struct Hangar {
+    pub classes: Vec<ShipClass>,
+}
+
+impl Default for ShipClass {
+    /// The all rounder every ship used to be.
+    fn default() -> Self {
+        Self {
+            name: "scout".to_string(),
+            wireframe: PLAYER_WIREFRAME,
+            hull: None,
+            overrides: Overrides::default(),
+            fuel: SHIP_FUEL,
+            health: SHIP_HEALTH,
+            weapon: Weapon::gun(),
+        }
+    }
+}
+
+impl ShipClass {
+    /// Slow to turn and hard to push around, with a lot of armor and a big slow gun.
+    pub fn heavy() -> Self {
+        Self {
+            name: "heavy".to_string(),
+            wireframe: HEAVY_WIREFRAME,
+            hull: Some(HEAVY_HULL),
+            overrides: Overrides {
+                mass: Some(1.8),
+                boost_power: Some(34.0),
+                rotational_acceleration: Some(7.0),
+                ..Overrides::default()
+            },
+            fuel: 26.0,
+            health: 150.0,
+            weapon: Weapon {
+                spawner: Spawner::new("shell"),
+                cooldown: 0.6,
+                recoil: 10.0,
+                ready_at: 0.0,
+            },
+        }
+    }
+
+    /// Light, twitchy and fragile, with a fast gun and a small tank.
+    pub fn dart() -> Self {
+        Self {
+            name: "dart".to_string(),
+            wireframe: DART_WIREFRAME,
+            hull: None,
+            overrides: Overrides {
+                mass: Some(0.7),
+                boost_power: Some(28.0),
+                rotational_acceleration: Some(14.0),
+                ..Overrides::default()
+            },
+            fuel: 14.0,
+            health: 70.0,
+            weapon: Weapon {
+                cooldown: 0.15,
+                recoil: 2.0,
+                ..Weapon::gun()
+            },
+        }
+    }
+
+    /// Turns `ship` into one of this class, full of fuel and health.
+    pub fn apply(&self, w: &mut hecs::World, ship: Entity) {
+        let outline = self.hull.unwrap_or(self.wireframe);
+        w.insert(
+            ship,
+            (
+                Wireframe(self.wireframe.into()),
+                Collides(Box::new(wireframe_to_polyline(outline))),
+                // rebuilds the collision shape at the current ship size
+                Scale::uniform(1.0),
+                self.overrides.clone(),
+                Fuel::new(self.fuel),
+                Health::new(self.health),
+                self.weapon.clone(),
+            ),
+        )
+        .unwrap();
+        match self.hull {
+            Some(hull) => w.insert_one(ship, Hull(hull.into())).unwrap(),
+            None => {
+                let _ = w.remove_one::<Hull>(ship);
+            }
+        }
+    }
+
+    /// One line for the ship select screen.
+    pub fn summary(&self) -> String {
+        let handling = self.overrides.apply(&Settings::default());
+        format!(
+            "mass {}  boost {}  turn {}  fuel {}s  health {}  fires every {}s",
+            self.overrides.mass(),
+            handling.boost_power,
+            handling.rotational_acceleration,
+            self.fuel,
+            self.health,
+            self.weapon.cooldown
+        )
+    }
+}
+
+impl Default for Hangar {
+    fn default() -> Self {
+        Self {
+            classes: vec![ShipClass::default(), ShipClass::heavy(), ShipClass::dart()],
+        }
+    }
+}
+
+impl Hangar {
+    /// The built in classes with the ones in `text` added, replacing any with the same name.
+    ///
+    /// After a `HANGAR_HEADER` line each line is `ship <name> <field=value>...`. Fields are
+    /// `shape` and `hull` (names from `snapshot::SHAPES`), `fuel`, `health`, `weapon` (a
+    /// `Registry` spawner id), `cooldown`, `recoil` and any field of `Overrides`. New classes
+    /// start from the scout and fields left out keep its values.
+    pub fn load(text: &str) -> Result<Self, DataError> {
+        let mut lines = text.lines().enumerate();
+        match lines.next() {
+            Some((_, HANGAR_HEADER)) => {}
+            _ => return Err(DataError::MissingHeader("ships")),
+        }
+
+        let mut ret = Self::default();
+        for (i, line) in lines {
+            let line_number = i + 1;
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                [] => {}
+                ["ship", name, fields @ ..] => {
+                    let existing = ret.classes.iter().position(|c| c.name == *name);
+                    let mut class = existing.map(|i| ret.classes[i].clone()).unwrap_or_default();
+                    class.name = name.to_string();
+                    parse_class(line_number, fields, &mut class)?;
+                    match existing {
+                        Some(i) => ret.classes[i] = class,
+                        None => ret.classes.push(class),
+                    }
+                }
+                [kind, ..] => {
+                    return Err(DataError::UnknownLine(
+                        "ships",
+                        line_number,
+                        kind.to_string(),
+                    ))
+                }
+            }
+        }
+        Ok(ret)
+    }
+}
+
+impl DataFile for Hangar {
+    fn load(text: &str) -> Result<Self, DataError> {
+        Hangar::load(text)
+    }
+}
+
+fn parse_class(line: usize, fields: &[&str], class: &mut ShipClass) -> Result<(), DataError> {
+    for field in fields {
+        let mut split = field.splitn(2, '=');
+        let name = split.next().unwrap();
+        let value = split.next().unwrap_or("");
+        let bad = || DataError::BadValue("ships", line, name.to_string());
+        match name {
+            "shape" => class.wireframe = snapshot::shape_by_name(value).ok_or_else(bad)?,
+            "hull" => class.hull = Some(snapshot::shape_by_name(value).ok_or_else(bad)?),
+            "fuel" => class.fuel = value.parse().map_err(|_| bad())?,
+            "health" => class.health = value.parse().map_err(|_| bad())?,
+            "weapon" => class.weapon.spawner = Spawner::new(value.to_string()),
+            "cooldown" => class.weapon.cooldown = value.parse().map_err(|_| bad())?,
+            "recoil" => class.weapon.recoil = value.parse().map_err(|_| bad())?,
+            _ => match class.overrides.field(name) {
+                Some(field) => {
+                    let value = value.parse().map_err(|_| bad())?;
+                    if !Overrides::allows(name, value) {
+                        return Err(bad());
+                    }
+                    *field = Some(value);
+                }
+                None => return Err(DataError::UnknownField("ships", line, name.to_string())),
+            },
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn hangar_loads_over_builtins() {
+    let text = format!(
+        "{}\n\nship heavy health=200\nship brick shape=heavy mass=5 weapon=shell\n",
+        HANGAR_HEADER
+    );
+    let hangar = Hangar::load(&text).unwrap();
+    let names: Vec<&str> = hangar.classes.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["scout", "heavy", "dart", "brick"]);
+    assert_eq!(hangar.classes[1].health, 200.0);
+    assert_eq!(hangar.classes[1].overrides.mass, Some(1.8));
+    let brick = &hangar.classes[3];
+    assert_eq!(brick.overrides.mass, Some(5.0));
+    assert_eq!(brick.fuel, SHIP_FUEL);
+    assert_eq!(brick.weapon.spawner, Spawner::new("shell"));
+
+    let typo = format!("{}\nship brick mas=5\n", HANGAR_HEADER);
+    assert_eq!(
+        Hangar::load(&typo).err(),
+        Some(DataError::UnknownField("ships", 2, "mas".into()))
+    );
+    let weightless = format!("{}\nship brick mass=0\n", HANGAR_HEADER);
+    assert_eq!(
+        Hangar::load(&weightless).err(),
+        Some(DataError::BadValue("ships", 2, "mass".into()))
+    );
+}
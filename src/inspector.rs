@@ -0,0 +1,280 @@
+use crate::assist::Assist;
+use crate::cave::CaveChunk;
+use crate::constants::{Overrides, Settings, WORLD_HEIGHT};
+use crate::fill::Fill;
+use crate::kinematic::Kinematic;
+use crate::mortal::Mortal;
+use crate::particles::{Emitter, Particles};
+use crate::pickup::{Fuel, Pickup, PickupDrops, PowerUps};
+use crate::pilot::Bot;
+use crate::scale::{LineWidth, Scale};
+use crate::spawner::Registry;
+use crate::system::{
+    Background, Boost, Collides, Contacts, Controls, Drag, Follow, Grav, Hull, Input, Pos, Rot,
+    RotVel, Time, Vel, Wireframe,
+};
+use crate::weapon::{Health, Hits, Projectile, Weapon};
+use crate::wind::WindZone;
+use hecs::{Entity, EntityBuilder};
+use macroquad::prelude::*;
+use macroquad::ui::{hash, widgets::Window, Ui};
+use parry2d::shape::Polyline;
+
+/// While this exists the inspector window is shown and clicking in the world selects the nearest
+/// entity. Toggled with F2.
+#[derive(Debug, Clone, Default)]
+pub struct Inspector {
+    pub selected: Option<Entity>,
+}
+
+/// meters, clicks further than this from every entity select nothing
+const PICK_RADIUS: f32 = 20.0;
+
+/// meters, clones appear this far to the right of the original
+const CLONE_OFFSET: f32 = 20.0;
+
+/// Changes requested from inside the window, applied once it's closed over.
+enum Action {
+    Select(Entity),
+    Despawn(Entity),
+    Clone(Entity),
+    SetGrav(Entity, bool),
+    SetDrag(Entity, bool),
+}
+
+impl Inspector {
+    pub fn enabled(w: &hecs::World) -> bool {
+        w.query::<(&Inspector,)>().iter().next().is_some()
+    }
+
+    pub fn toggle(w: &mut hecs::World) {
+        let existing: Vec<Entity> = w
+            .query::<(&Inspector,)>()
+            .iter()
+            .map(|(ent, _)| ent)
+            .collect();
+        if existing.is_empty() {
+            w.spawn((Inspector::default(),));
+        }
+        for ent in existing {
+            w.despawn(ent).unwrap();
+        }
+    }
+
+    /// Selects the entity closest to `point`, in meters.
+    pub fn pick(w: &mut hecs::World, point: Vec2) {
+        let nearest = w
+            .query::<(&Pos,)>()
+            .iter()
+            .map(|(ent, (pos,))| (ent, (pos.0 - point).length()))
+            .filter(|(_, distance)| *distance < PICK_RADIUS)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .map(|(ent, _)| ent);
+        for (_, (inspector,)) in w.query_mut::<(&mut Inspector,)>() {
+            inspector.selected = nearest;
+        }
+    }
+
+    fn selected(w: &hecs::World) -> Option<Entity> {
+        w.query::<(&Inspector,)>()
+            .iter()
+            .next()
+            .and_then(|(_, (inspector,))| inspector.selected)
+            .filter(|ent| w.contains(*ent))
+    }
+
+    /// Rings the selected entity. Expects the world camera to be set.
+    pub fn draw_world(w: &hecs::World) {
+        let pos = match Self::selected(w).and_then(|ent| w.get::<Pos>(ent).ok()) {
+            Some(pos) => pos.0,
+            None => return,
+        };
+        let meters_per_screen = 1.0 / WORLD_HEIGHT;
+        let p = pos * meters_per_screen;
+        draw_circle_lines(
+            p.x,
+            p.y,
+            PICK_RADIUS * meters_per_screen,
+            0.5 * meters_per_screen,
+            YELLOW,
+        );
+    }
+
+    /// The entity list and an editor for the selected entity.
+    pub fn ui(w: &mut hecs::World, ui: &mut Ui) {
+        if !Self::enabled(w) {
+            return;
+        }
+        let selected = Self::selected(w);
+        let mut actions: Vec<Action> = Vec::new();
+
+        let world: &hecs::World = w;
+        Window::new(hash!(), vec2(420.0, 40.0), vec2(400.0, 500.0))
+            .label("inspector")
+            .ui(ui, |ui| {
+                if let Some(ent) = selected {
+                    edit(world, ent, ui, &mut actions);
+                    ui.separator();
+                }
+
+                let mut all: Vec<Entity> =
+                    world.query::<()>().iter().map(|(ent, ())| ent).collect();
+                all.sort_by_key(|ent| ent.id());
+                for ent in all {
+                    let marker = if Some(ent) == selected { ">" } else { " " };
+                    let row = format!("{}{} {}", marker, ent.id(), describe(world, ent).join(" "));
+                    if ui.button(None, row.as_str()) {
+                        actions.push(Action::Select(ent));
+                    }
+                }
+            });
+
+        for action in actions {
+            match action {
+                Action::Select(ent) => {
+                    for (_, (inspector,)) in w.query_mut::<(&mut Inspector,)>() {
+                        inspector.selected = Some(ent);
+                    }
+                }
+                Action::Despawn(ent) => {
+                    let _ = w.despawn(ent);
+                }
+                Action::Clone(ent) => {
+                    let copy = clone_entity(w, ent);
+                    for (_, (inspector,)) in w.query_mut::<(&mut Inspector,)>() {
+                        inspector.selected = Some(copy);
+                    }
+                }
+                Action::SetGrav(ent, true) => w.insert_one(ent, Grav).unwrap(),
+                Action::SetGrav(ent, false) => {
+                    let _ = w.remove_one::<Grav>(ent);
+                }
+                Action::SetDrag(ent, true) => w.insert_one(ent, Drag).unwrap(),
+                Action::SetDrag(ent, false) => {
+                    let _ = w.remove_one::<Drag>(ent);
+                }
+            }
+        }
+    }
+}
+
+/// Fields of the selected entity. Edits go straight into the components.
+fn edit(w: &hecs::World, ent: Entity, ui: &mut Ui, actions: &mut Vec<Action>) {
+    ui.label(None, &format!("entity {}", ent.id()));
+    if ui.button(None, "despawn") {
+        actions.push(Action::Despawn(ent));
+    }
+    ui.same_line(0.0);
+    if ui.button(None, "clone") {
+        actions.push(Action::Clone(ent));
+    }
+
+    if let Ok(mut pos) = w.get_mut::<Pos>(ent) {
+        ui.drag(hash!(), "pos x", None, &mut pos.0.x);
+        ui.drag(hash!(), "pos y", None, &mut pos.0.y);
+    }
+    if let Ok(mut vel) = w.get_mut::<Vel>(ent) {
+        ui.drag(hash!(), "vel x", None, &mut vel.0.x);
+        ui.drag(hash!(), "vel y", None, &mut vel.0.y);
+    }
+    if let Ok(mut rot) = w.get_mut::<Rot>(ent) {
+        ui.drag(hash!(), "rot", None, &mut rot.0);
+    }
+    if let Ok(mut rv) = w.get_mut::<RotVel>(ent) {
+        ui.drag(hash!(), "rotvel", None, &mut rv.0);
+    }
+    if let Ok(mut boost) = w.get_mut::<Boost>(ent) {
+        ui.drag(hash!(), "boost", None, &mut boost.0);
+    }
+    if let Ok(mut scale) = w.get_mut::<Scale>(ent) {
+        ui.drag(hash!(), "scale x", None, &mut scale.size.x);
+        ui.drag(hash!(), "scale y", None, &mut scale.size.y);
+    }
+    if let Ok(mut lw) = w.get_mut::<LineWidth>(ent) {
+        ui.drag(hash!(), "line width", None, &mut lw.0);
+    }
+    if let Ok(mut color) = w.get_mut::<Color>(ent) {
+        ui.slider(hash!(), "red", 0.0..1.0, &mut color.r);
+        ui.slider(hash!(), "green", 0.0..1.0, &mut color.g);
+        ui.slider(hash!(), "blue", 0.0..1.0, &mut color.b);
+        ui.slider(hash!(), "alpha", 0.0..1.0, &mut color.a);
+    }
+    if let Ok(mut mortal) = w.get_mut::<Mortal>(ent) {
+        let now = Time::get(w).now;
+        ui.label(None, &format!("age {:.2}", mortal.age(now)));
+        let mut remaining = (mortal.erase_at - now) as f32;
+        ui.drag(hash!(), "seconds left", None, &mut remaining);
+        mortal.erase_at = now + remaining as f64;
+    }
+
+    let had_grav = w.get::<Grav>(ent).is_ok();
+    let mut grav = had_grav;
+    ui.checkbox(hash!(), "grav", &mut grav);
+    if grav != had_grav {
+        actions.push(Action::SetGrav(ent, grav));
+    }
+    let had_drag = w.get::<Drag>(ent).is_ok();
+    let mut drag = had_drag;
+    ui.checkbox(hash!(), "drag", &mut drag);
+    if drag != had_drag {
+        actions.push(Action::SetDrag(ent, drag));
+    }
+}
+
+/// Names of the components the inspector knows about.
+fn describe(w: &hecs::World, ent: Entity) -> Vec<&'static str> {
+    let mut ret = Vec::new();
+    macro_rules! has {
+        ($($t:ty),*) => {
+            $(
+                if w.get::<$t>(ent).is_ok() {
+                    ret.push(stringify!($t));
+                }
+            )*
+        };
+    }
+    has!(
+        Pos, Vel, Rot, RotVel, Boost, Grav, Drag, Color, Wireframe, Fill, Collides, Scale,
+        LineWidth, Mortal, Emitter, Kinematic, Input, Controls, Bot, Assist, WindZone, CaveChunk,
+        Follow, Camera2D, Settings, Time, Particles, Contacts, Background, Inspector, Registry,
+        Weapon, Projectile, Health, Hits, Pickup, PowerUps, Fuel, PickupDrops, Overrides, Hull
+    );
+    ret
+}
+
+/// Spawns a copy of `ent` a little to the side. Pilots aren't copied, the clone drifts until
+/// given `Controls` or a `Bot`.
+pub fn clone_entity(w: &mut hecs::World, ent: Entity) -> Entity {
+    let mut builder = EntityBuilder::new();
+    macro_rules! copy {
+        ($($t:ty),*) => {
+            $(
+                if let Ok(c) = w.get::<$t>(ent) {
+                    builder.add((*c).clone());
+                }
+            )*
+        };
+    }
+    copy!(
+        Vel, Rot, RotVel, Boost, Grav, Drag, Color, Wireframe, Fill, Scale, LineWidth, Mortal,
+        Emitter, Assist, WindZone, Health, Weapon, Pickup, PowerUps, Fuel, Overrides, Hull
+    );
+    if w.get::<Input>(ent).is_ok() {
+        builder.add(Input::default());
+    }
+    if let Ok(pos) = w.get::<Pos>(ent) {
+        builder.add(Pos(pos.0 + vec2(CLONE_OFFSET, 0.0)));
+    }
+    if let Ok(kinematic) = w.get::<Kinematic>(ent) {
+        builder.add(Kinematic {
+            origin: kinematic.origin + vec2(CLONE_OFFSET, 0.0),
+            ..(*kinematic).clone()
+        });
+    }
+    if let Ok(collides) = w.get::<Collides>(ent) {
+        if let Some(polyline) = collides.0.as_shape::<Polyline>() {
+            builder.add(Collides(Box::new(polyline.clone())));
+        }
+    }
+    w.spawn(builder.build())
+}
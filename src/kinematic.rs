@@ -0,0 +1,120 @@
+use crate::system::{Pos, RotVel, Time, Vel};
+use core::f32::consts::TAU;
+use macroquad::prelude::*;
+
+/// A body moved by a script rather than by forces. Collisions push other bodies away but never
+/// move a kinematic body.
+///
+/// The script is followed by setting `Vel` and `RotVel` each frame so the rest of the physics
+/// sees kinematic bodies moving like anything else.
+#[derive(Debug, Clone)]
+pub struct Kinematic {
+    pub motion: Motion,
+    /// where the motion is anchored
+    pub origin: Vec2,
+    /// seconds of world time at which the motion started
+    pub start: f64,
+}
+
+#[derive(Debug, Clone)]
+pub enum Motion {
+    /// never moves, used for level geometry
+    Still,
+    /// visit each point in order at constant speed, then return to the first
+    /// points are relative to origin
+    Waypoints {
+        points: Vec<Vec2>,
+        /// meters per second
+        speed: f32,
+    },
+    /// swing back and forth through origin
+    Sine {
+        /// meters, furthest offset from origin
+        amplitude: Vec2,
+        /// seconds per full swing
+        period: f32,
+    },
+    /// spin in place around origin
+    Rotate {
+        /// radians per second
+        rate: f32,
+    },
+}
+
+impl Kinematic {
+    pub fn new(motion: Motion, origin: Vec2) -> Self {
+        Self {
+            motion,
+            origin,
+            start: 0.0,
+        }
+    }
+
+    pub fn still(origin: Vec2) -> Self {
+        Self::new(Motion::Still, origin)
+    }
+
+    pub fn system(w: &mut hecs::World) {
+        let Time { now: time, delta: delta_t } = Time::get(w);
+        if delta_t <= 0.0 {
+            return;
+        }
+
+        for (_id, (k, pos, vel, rv)) in
+            w.query_mut::<(&Kinematic, &Pos, &mut Vel, Option<&mut RotVel>)>()
+        {
+            // aim for where the script says we should be at the end of this tick
+            let t = (time - k.start) as f32 + delta_t;
+            let target = k.origin + k.motion.offset(t);
+            vel.0 = (target - pos.0) / delta_t;
+            if let Some(rv) = rv {
+                rv.0 = k.motion.rotation_rate();
+            }
+        }
+    }
+}
+
+impl Motion {
+    /// offset from origin `t` seconds after the motion started
+    pub fn offset(&self, t: f32) -> Vec2 {
+        match self {
+            Motion::Still | Motion::Rotate { .. } => vec2(0.0, 0.0),
+            Motion::Waypoints { points, speed } => waypoint_offset(points, *speed, t),
+            Motion::Sine { amplitude, period } => *amplitude * (t * TAU / *period).sin(),
+        }
+    }
+
+    /// radians per second
+    pub fn rotation_rate(&self) -> f32 {
+        match self {
+            Motion::Rotate { rate } => *rate,
+            _ => 0.0,
+        }
+    }
+}
+
+fn waypoint_offset(points: &[Vec2], speed: f32, t: f32) -> Vec2 {
+    let segments = || points.iter().zip(points.iter().cycle().skip(1));
+    let total: f32 = segments().map(|(a, b)| (*b - *a).length()).sum();
+    if total <= 0.0 {
+        return points.first().cloned().unwrap_or(vec2(0.0, 0.0));
+    }
+
+    let mut traveled = (t * speed).rem_euclid(total);
+    for (a, b) in segments() {
+        let len = (*b - *a).length();
+        if traveled <= len && len > 0.0 {
+            return *a + (*b - *a) * (traveled / len);
+        }
+        traveled -= len;
+    }
+    points[0]
+}
+
+#[test]
+fn waypoints_loop() {
+    let square = [vec2(0.0, 0.0), vec2(1.0, 0.0), vec2(1.0, 1.0), vec2(0.0, 1.0)];
+    assert_eq!(waypoint_offset(&square, 1.0, 0.5), vec2(0.5, 0.0));
+    assert_eq!(waypoint_offset(&square, 1.0, 2.0), vec2(1.0, 1.0));
+    assert_eq!(waypoint_offset(&square, 1.0, 4.5), vec2(0.5, 0.0));
+}
@@ -0,0 +1,274 @@
+use crate::constants::*;
+use crate::fill::{Fill, WireframeError};
+use crate::kinematic::{Kinematic, Motion};
+use crate::particles::Emitter;
+use crate::pickup::{Pickup, PickupDrops, PickupKind};
+use crate::scale::Scale;
+use crate::score::{Hazard, SpawnPoint};
+use crate::script::Script;
+use crate::spawner::Registry;
+use crate::system::{Collides, Pos, Rot, RotVel, Vel, Wireframe};
+use crate::util::{scaled_polyline, wireframe_to_polyline};
+use crate::wind::{WindField, WindStreaks, WindZone};
+use core::fmt;
+use macroquad::prelude::*;
+
+/// A kinematic obstacle.
+#[derive(Debug, Clone)]
+pub struct Platform {
+    pub origin: Vec2,
+    pub shape: &'static [(f32, f32)],
+    /// stretches shape along x and y
+    pub scale: Vec2,
+    pub motion: Motion,
+    /// draw as a solid, shape must be closed and must not cross itself
+    pub fill: Option<Color>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LevelError {
+    Wireframe(WireframeError),
+    /// no emitter with this id in the `Registry`
+    UnknownEmitter(String),
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::Wireframe(e) => write!(f, "{}", e),
+            LevelError::UnknownEmitter(id) => write!(f, "no emitter named {}", id),
+        }
+    }
+}
+
+impl From<WireframeError> for LevelError {
+    fn from(e: WireframeError) -> Self {
+        LevelError::Wireframe(e)
+    }
+}
+
+/// Everything about a play area that isn't a player.
+pub struct Level {
+    pub walls: &'static [(f32, f32)],
+    /// (position, zone)
+    pub wind_zones: Vec<(Vec2, WindZone)>,
+    pub platforms: Vec<Platform>,
+    /// (position, `Registry` emitter id) particle effects fixed in place
+    pub emitters: Vec<(Vec2, &'static str)>,
+    /// (file name, source) level logic, see `script`
+    pub script: Option<(&'static str, &'static str)>,
+    /// where each ship starts a round, by player
+    pub spawns: Vec<Vec2>,
+    /// (position, shape) obstacles a ship scores against, see `score::Match`
+    pub hazards: Vec<(Vec2, &'static [(f32, f32)])>,
+    /// (position, kind) pickups lying in the level from the start
+    pub pickups: Vec<(Vec2, PickupKind)>,
+    /// random pickups dropped during play
+    pub drops: Option<PickupDrops>,
+    /// a loop through the level that stays clear of obstacles, followed by bots
+    pub race_line: Vec<Vec2>,
+}
+
+impl Default for Level {
+    fn default() -> Self {
+        Self {
+            walls: BOUNDS_WIREFRAME,
+            wind_zones: vec![
+                (
+                    vec2(-WORLD_HEIGHT * 0.35, 0.0),
+                    WindZone::rect(
+                        WORLD_HEIGHT * 0.2,
+                        WORLD_HEIGHT * 0.8,
+                        WindField::Constant(vec2(0.0, 20.0)),
+                    ),
+                ),
+                (
+                    vec2(WORLD_HEIGHT * 0.3, WORLD_HEIGHT * 0.25),
+                    WindZone::rect(
+                        WORLD_HEIGHT * 0.3,
+                        WORLD_HEIGHT * 0.3,
+                        WindField::Vortex { strength: 25.0 },
+                    ),
+                ),
+                (
+                    vec2(WORLD_HEIGHT * 0.3, -WORLD_HEIGHT * 0.25),
+                    WindZone::rect(
+                        WORLD_HEIGHT * 0.3,
+                        WORLD_HEIGHT * 0.3,
+                        WindField::Turbulence {
+                            strength: 30.0,
+                            scale: 40.0,
+                            speed: 0.5,
+                        },
+                    ),
+                ),
+            ],
+            platforms: vec![
+                Platform {
+                    origin: vec2(0.0, -WORLD_HEIGHT * 0.3),
+                    shape: PLATFORM_WIREFRAME,
+                    scale: vec2(1.0, 1.0),
+                    fill: Some(color_pallet()[6]),
+                    motion: Motion::Sine {
+                        amplitude: vec2(WORLD_HEIGHT * 0.15, 0.0),
+                        period: 8.0,
+                    },
+                },
+                Platform {
+                    origin: vec2(0.0, WORLD_HEIGHT * 0.3),
+                    shape: PLATFORM_WIREFRAME,
+                    scale: vec2(1.5, 1.0),
+                    fill: Some(color_pallet()[6]),
+                    motion: Motion::Rotate { rate: 0.5 },
+                },
+                Platform {
+                    origin: vec2(-WORLD_HEIGHT * 0.1, 0.0),
+                    shape: PLATFORM_WIREFRAME,
+                    scale: vec2(0.5, 1.0),
+                    fill: None,
+                    motion: Motion::Waypoints {
+                        points: vec![
+                            vec2(0.0, 0.0),
+                            vec2(0.0, WORLD_HEIGHT * 0.15),
+                            vec2(WORLD_HEIGHT * 0.1, WORLD_HEIGHT * 0.15),
+                        ],
+                        speed: 20.0,
+                    },
+                },
+            ],
+            emitters: vec![(vec2(-WORLD_HEIGHT * 0.35, -WORLD_HEIGHT * 0.48), "vent")],
+            script: Some(("levels/arena.rhai", include_str!("../levels/arena.rhai"))),
+            spawns: vec![
+                vec2(-WORLD_HEIGHT * 0.2, -WORLD_HEIGHT * 0.05),
+                vec2(WORLD_HEIGHT * 0.2, -WORLD_HEIGHT * 0.05),
+            ],
+            hazards: vec![(
+                vec2(WORLD_HEIGHT * 0.15, -WORLD_HEIGHT * 0.5),
+                SPIKES_WIREFRAME,
+            )],
+            pickups: vec![
+                (
+                    vec2(-WORLD_HEIGHT * 0.2, WORLD_HEIGHT * 0.35),
+                    PickupKind::Fuel,
+                ),
+                (
+                    vec2(WORLD_HEIGHT * 0.1, WORLD_HEIGHT * 0.05),
+                    PickupKind::Repair,
+                ),
+            ],
+            drops: Some(PickupDrops {
+                center: vec2(0.0, 0.0),
+                half_size: vec2(WORLD_HEIGHT * 0.4, WORLD_HEIGHT * 0.4),
+                interval: 10.0,
+                max: 3,
+                next_at: 10.0,
+                seed: 1,
+                dropped: 0,
+            }),
+            race_line: vec![
+                vec2(0.0, -WORLD_HEIGHT * 0.15),
+                vec2(WORLD_HEIGHT * 0.1, WORLD_HEIGHT * 0.05),
+                vec2(0.0, WORLD_HEIGHT * 0.15),
+                vec2(-WORLD_HEIGHT * 0.2, WORLD_HEIGHT * 0.35),
+                vec2(-WORLD_HEIGHT * 0.2, -WORLD_HEIGHT * 0.4),
+            ],
+        }
+    }
+}
+
+impl Level {
+    /// Just the walls.
+    pub fn empty() -> Self {
+        Self {
+            walls: BOUNDS_WIREFRAME,
+            wind_zones: Vec::new(),
+            platforms: Vec::new(),
+            emitters: Vec::new(),
+            script: None,
+            spawns: Vec::new(),
+            hazards: Vec::new(),
+            pickups: Vec::new(),
+            drops: None,
+            race_line: Vec::new(),
+        }
+    }
+
+    /// Fails without spawning anything if a filled shape can't be filled or an emitter isn't in
+    /// the world's `Registry`.
+    pub fn spawn(&self, world: &mut hecs::World) -> Result<(), LevelError> {
+        let fills = self
+            .platforms
+            .iter()
+            .map(|p| p.fill.map(|color| Fill::new(p.shape, color)).transpose())
+            .collect::<Result<Vec<Option<Fill>>, WireframeError>>()?;
+        let emitters = self
+            .emitters
+            .iter()
+            .map(|(pos, id)| match Registry::emitter(world, id) {
+                Some(_) => Ok((Pos(*pos), Rot(0.), Emitter::new(*id))),
+                None => Err(LevelError::UnknownEmitter(id.to_string())),
+            })
+            .collect::<Result<Vec<_>, LevelError>>()?;
+
+        let walls = (
+            Collides(Box::new(wireframe_to_polyline(self.walls))),
+            Wireframe(self.walls.into()),
+            Pos(vec2(0., 0.)),
+            Vel(vec2(0., 0.)),
+            Rot(0.),
+            Kinematic::still(vec2(0., 0.)),
+            color_pallet()[2],
+        );
+        world.spawn(walls);
+
+        for (platform, fill) in self.platforms.iter().zip(fills) {
+            let ent = world.spawn((
+                Collides(Box::new(scaled_polyline(platform.shape, platform.scale))),
+                Wireframe(platform.shape.into()),
+                Scale::new(platform.scale),
+                Pos(platform.origin + platform.motion.offset(0.0)),
+                Vel(vec2(0., 0.)),
+                Rot(0.),
+                RotVel(0.),
+                Kinematic::new(platform.motion.clone(), platform.origin),
+                color_pallet()[5],
+            ));
+            if let Some(fill) = fill {
+                world.insert_one(ent, fill).unwrap();
+            }
+        }
+
+        for (pos, shape) in &self.hazards {
+            world.spawn((
+                Collides(Box::new(wireframe_to_polyline(shape))),
+                Wireframe((*shape).into()),
+                Pos(*pos),
+                Vel(vec2(0., 0.)),
+                Rot(0.),
+                Kinematic::still(*pos),
+                Hazard,
+                color_pallet()[4],
+            ));
+        }
+        for (i, pos) in self.spawns.iter().enumerate() {
+            world.spawn((Pos(*pos), SpawnPoint(i)));
+        }
+        for (pos, kind) in &self.pickups {
+            Pickup::spawn(world, *pos, *kind);
+        }
+        if let Some(drops) = &self.drops {
+            world.spawn((drops.clone(),));
+        }
+
+        for (pos, zone) in &self.wind_zones {
+            world.spawn((Pos(*pos), zone.clone(), WindStreaks::default()));
+        }
+        for emitter in emitters {
+            world.spawn(emitter);
+        }
+        if let Some((file, source)) = self.script {
+            world.spawn((Script::load(file, source),));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,31 @@
+extern crate alloc;
+extern crate core;
+
+pub mod assist;
+pub mod cave;
+pub mod commands;
+pub mod constants;
+pub mod debug;
+pub mod env;
+pub mod fill;
+pub mod hangar;
+pub mod inspector;
+pub mod kinematic;
+pub mod level;
+pub mod menu;
+pub mod mortal;
+pub mod net;
+pub mod particles;
+pub mod pickup;
+pub mod pilot;
+pub mod scale;
+pub mod schedule;
+pub mod score;
+pub mod script;
+pub mod snapshot;
+pub mod spawner;
+pub mod spectate;
+pub mod system;
+pub mod util;
+pub mod weapon;
+pub mod wind;
@@ -1,26 +1,93 @@
-extern crate core;
-extern crate alloc;
-
+use heli::net::{NetGame, NetOptions};
+use heli::spectate::{Spectator, SpectatorServer};
+use heli::{menu, system};
 use macroquad::prelude::*;
-
-mod constants;
-mod system;
-mod util;
-mod spawner;
-mod mortal;
+use std::net::SocketAddr;
 
 #[macroquad::main(window_conf)]
 async fn main() {
-    let mut heli = system::Heli::new();
+    let args: Vec<String> = std::env::args().collect();
+
+    if let Some(addr) = address_after(&args, "--spectate") {
+        let connected = addr.and_then(|a| Spectator::connect(a).map_err(|e| e.to_string()));
+        let mut spectator = match connected {
+            Ok(spectator) => spectator,
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+        };
+        while !is_key_down(KeyCode::Escape) && !is_key_down(KeyCode::Q) {
+            spectator.update();
+            spectator.draw();
+            next_frame().await;
+        }
+        return;
+    }
+
+    // lets spectators watch whatever is played here
+    let mut server = match address_after(&args, "--serve") {
+        Some(addr) => {
+            match addr.and_then(|a| SpectatorServer::bind(a).map_err(|e| e.to_string())) {
+                Ok(server) => Some(server),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return;
+                }
+            }
+        }
+        None => None,
+    };
+
+    if let Some(options) = NetOptions::from_args(&args) {
+        let mut game = match options.and_then(|o| NetGame::new(&o).map_err(|e| e.to_string())) {
+            Ok(game) => game,
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+        };
+        while !game.should_quit() {
+            game.update();
+            if let Some(server) = &mut server {
+                server.publish(game.heli().world());
+            }
+            game.draw();
+            game.ui();
+            next_frame().await;
+        }
+        return;
+    }
+
+    let options = match menu::choose().await {
+        Some(options) => options,
+        None => return,
+    };
+    // don't let the key that started the game count as input
+    next_frame().await;
+
+    let mut heli = system::Heli::new(&options);
 
     while !heli.should_quit() {
         heli.update();
+        if let Some(server) = &mut server {
+            server.publish(heli.world());
+        }
         heli.draw();
         heli.ui();
         next_frame().await;
     }
 }
 
+/// None if `flag` isn't given.
+fn address_after(args: &[String], flag: &str) -> Option<Result<SocketAddr, String>> {
+    let at = args.iter().position(|a| a == flag)?;
+    Some(match args.get(at + 1) {
+        Some(addr) => addr.parse().map_err(|e| format!("{}: {}", addr, e)),
+        None => Err(format!("usage: {} <address>", flag)),
+    })
+}
+
 fn window_conf() -> Conf {
     Conf {
         window_title: "Heli".to_owned(),
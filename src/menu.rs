@@ -0,0 +1,135 @@
+use crate::hangar::{Hangar, ShipClass};
+use crate::pilot::BotKind;
+use crate::snapshot;
+use macroquad::prelude::*;
+use macroquad::ui::{hash, root_ui, widgets::Window};
+
+/// What the player picked before starting.
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub mode: Mode,
+    /// who flies player 2
+    pub opponent: Opponent,
+    /// by player, ships without a class are scouts
+    pub ships: Vec<ShipClass>,
+}
+
+/// extra ship classes, loaded before the ship select screen if present
+const SHIPS: &str = "ships.txt";
+
+#[derive(Debug, Clone)]
+pub enum Mode {
+    /// the walled arena from `Level::default`
+    Arena,
+    /// endless tunnel generated from a seed
+    Cave { seed: u32 },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Opponent {
+    Human,
+    Bot(BotKind),
+}
+
+impl Opponent {
+    const ALL: [Opponent; 4] = [
+        Opponent::Human,
+        Opponent::Bot(BotKind::Hover),
+        Opponent::Bot(BotKind::Waypoints),
+        Opponent::Bot(BotKind::Race),
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Opponent::Human => "human",
+            Opponent::Bot(BotKind::Hover) => "bot: hover",
+            Opponent::Bot(BotKind::Waypoints) => "bot: waypoints",
+            Opponent::Bot(BotKind::Race) => "bot: race",
+        }
+    }
+}
+
+/// Shows the menu until the player picks something. Returns None if they asked to quit.
+pub async fn choose() -> Option<Options> {
+    let mut seed = String::from("1");
+    let mut opponent = 0;
+    loop {
+        if is_key_down(KeyCode::Escape) || is_key_down(KeyCode::Q) {
+            return None;
+        }
+
+        let mut chosen = None;
+        clear_background(BLACK);
+        Window::new(hash!(), vec2(10.0, 40.0), vec2(300.0, 200.0))
+            .label("Heli")
+            .ui(&mut root_ui(), |ui| {
+                let label = format!("player 2: {}", Opponent::ALL[opponent].name());
+                if ui.button(None, &label) {
+                    opponent = (opponent + 1) % Opponent::ALL.len();
+                }
+                if ui.button(None, "Arena") {
+                    chosen = Some(Mode::Arena);
+                }
+                ui.input_text(hash!(), "seed", &mut seed);
+                if ui.button(None, "Cave") {
+                    chosen = Some(Mode::Cave {
+                        seed: parse_seed(&seed),
+                    });
+                }
+            });
+
+        if let Some(mode) = chosen {
+            // don't let the click that picked the mode count on the next screen
+            next_frame().await;
+            return Some(Options {
+                mode,
+                opponent: Opponent::ALL[opponent],
+                ships: choose_ships(2).await?,
+            });
+        }
+        next_frame().await;
+    }
+}
+
+/// Lets each player pick a ship class. Returns None if they asked to quit.
+async fn choose_ships(players: usize) -> Option<Vec<ShipClass>> {
+    let hangar = snapshot::load_or_default::<Hangar>(SHIPS);
+    let classes = &hangar.classes;
+    let mut picked = vec![0; players];
+    loop {
+        if is_key_down(KeyCode::Escape) || is_key_down(KeyCode::Q) {
+            return None;
+        }
+
+        let mut done = false;
+        clear_background(BLACK);
+        Window::new(hash!(), vec2(10.0, 40.0), vec2(560.0, 240.0))
+            .label("Ships")
+            .ui(&mut root_ui(), |ui| {
+                for (player, index) in picked.iter_mut().enumerate() {
+                    let label = format!("player {}: {}", player + 1, classes[*index].name);
+                    if ui.button(None, &label) {
+                        *index = (*index + 1) % classes.len();
+                    }
+                    ui.label(None, &classes[*index].summary());
+                }
+                if ui.button(None, "Fly") {
+                    done = true;
+                }
+            });
+
+        if done {
+            return Some(picked.iter().map(|i| classes[*i].clone()).collect());
+        }
+        next_frame().await;
+    }
+}
+
+/// Numbers are used as is so seeds are easy to share, anything else is hashed.
+fn parse_seed(seed: &str) -> u32 {
+    let seed = seed.trim();
+    seed.parse().unwrap_or_else(|_| {
+        seed.bytes()
+            .fold(0x811c_9dc5u32, |h, b| (h ^ b as u32).wrapping_mul(0x0100_0193))
+    })
+}
@@ -1,23 +1,138 @@
+use crate::commands::Commands;
+use crate::particles::Emitter;
+use crate::spawner::Spawner;
+use crate::system::Time;
 use hecs::Entity;
-use macroquad::prelude::*;
 
+#[derive(Clone)]
 pub struct Mortal {
+    pub born_at: f64,
     pub erase_at: f64,
+    /// how the entity looks as it ages, None leaves it untouched until it pops out of existence
+    pub fade: Option<Fade>,
+    /// runs just before the entity is despawned, while its components can still be read
+    pub on_expire: Option<Spawner>,
+}
+
+/// Visual change over a `Mortal`'s life. Applied when drawing, the components themselves are left
+/// alone.
+#[derive(Debug, Clone)]
+pub struct Fade {
+    /// multiplies `Color` alpha
+    pub alpha: Curve,
+    /// multiplies `Wireframe` size
+    pub scale: Curve,
+}
+
+/// Goes from start to end as age goes from 0 to 1.
+#[derive(Debug, Clone)]
+pub struct Curve {
+    pub start: f32,
+    pub end: f32,
+    /// 1.0 is linear, higher values hold near start longer
+    pub power: f32,
 }
 
 impl Mortal {
+    /// lives for `lifetime` seconds from `now`
+    pub fn new(now: f64, lifetime: f64) -> Self {
+        Self {
+            born_at: now,
+            erase_at: now + lifetime,
+            fade: None,
+            on_expire: None,
+        }
+    }
+
+    pub fn with_fade(self, fade: Fade) -> Self {
+        Self {
+            fade: Some(fade),
+            ..self
+        }
+    }
+
+    pub fn on_expire(self, spawner: Spawner) -> Self {
+        Self {
+            on_expire: Some(spawner),
+            ..self
+        }
+    }
+
+    /// 0.0 at birth, 1.0 at expiry
+    pub fn age(&self, now: f64) -> f32 {
+        let span = self.erase_at - self.born_at;
+        if span <= 0.0 {
+            return 1.0;
+        }
+        (((now - self.born_at) / span) as f32).clamp(0.0, 1.0)
+    }
+
+    /// (alpha multiplier, scale multiplier)
+    pub fn visual(&self, now: f64) -> (f32, f32) {
+        match &self.fade {
+            Some(fade) => {
+                let age = self.age(now);
+                (fade.alpha.at(age), fade.scale.at(age))
+            }
+            None => (1.0, 1.0),
+        }
+    }
+
     pub fn system(w: &mut hecs::World) {
-        let time = get_time();
+        let time = Time::get(w).now;
 
         let mut eol: Vec<Entity> = Vec::new();
+        let mut hooks: Vec<(Entity, Spawner)> = Vec::new();
         for (ent, (m,)) in w.query_mut::<(&Mortal,)>() {
             if m.erase_at <= time {
                 eol.push(ent);
+                if let Some(spawner) = &m.on_expire {
+                    hooks.push((ent, spawner.clone()));
+                }
             }
         }
 
+        Emitter::on_death(w, &eol);
+        for (ent, spawner) in hooks {
+            spawner.spawn(w, ent);
+        }
+        let mut commands = Commands::default();
         for ent in eol {
-            w.despawn(ent).unwrap();
+            commands.despawn(ent);
+        }
+        commands.submit(w);
+    }
+}
+
+impl Fade {
+    /// fade out while shrinking to nothing
+    pub fn vanish() -> Self {
+        Self {
+            alpha: Curve::linear(1.0, 0.0),
+            scale: Curve::linear(1.0, 0.0),
         }
     }
 }
+
+impl Curve {
+    pub fn linear(start: f32, end: f32) -> Self {
+        Self {
+            start,
+            end,
+            power: 1.0,
+        }
+    }
+
+    pub fn at(&self, age: f32) -> f32 {
+        self.start + (self.end - self.start) * age.powf(self.power)
+    }
+}
+
+#[test]
+fn age_and_fade() {
+    let m = Mortal::new(10.0, 2.0).with_fade(Fade::vanish());
+    assert_eq!(m.age(10.0), 0.0);
+    assert_eq!(m.age(11.0), 0.5);
+    assert_eq!(m.age(20.0), 1.0);
+    assert_eq!(m.visual(11.0), (0.5, 0.5));
+}
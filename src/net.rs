@@ -0,0 +1,660 @@
+//! Two player games between machines. Each peer simulates the whole game, sending only its own
+//! inputs over UDP. Frames run as soon as local input is known, guessing that the other pilot
+//! is still holding whatever they held last. When their real input turns out different the
+//! game rolls back to the state before that frame and simulates forward again. Both peers
+//! must load the same rules.txt and spawners.txt, each packet carries a hash of them and a peer
+//! with different ones is ignored.
+//!
+//! Try it on one machine with
+//! `heli --net 127.0.0.1:7000 127.0.0.1:7001 1` and `heli --net 127.0.0.1:7001 127.0.0.1:7000 2`,
+//! adding `--loss 0.2 --delay 6` to either to see how it copes with a bad connection.
+
+use crate::constants::Settings;
+use crate::menu::{Mode, Opponent, Options};
+use crate::particles::Particles;
+use crate::script::Script;
+use crate::snapshot;
+use crate::spawner::Registry;
+use crate::system::{data_hash, ships, Controls, Heli, Input, Quit, FIXED_STEP};
+use crate::util::Rng;
+use crate::wind::{WindStreaks, WindZone};
+use hecs::Entity;
+use macroquad::prelude::*;
+use std::collections::VecDeque;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, UdpSocket};
+
+/// first byte of every packet
+const MAGIC: u8 = b'H';
+
+/// inputs in one packet at most
+const MAX_INPUTS: usize = 64;
+
+/// frames run ahead of the latest confirmed input before waiting for the other peer
+const MAX_PREDICTION: u32 = 30;
+
+/// Sends and receives whole packets, without blocking.
+pub trait Link {
+    fn send(&mut self, packet: &[u8]);
+
+    /// Everything that arrived since the last call. Called once per frame.
+    fn recv(&mut self) -> Vec<Vec<u8>>;
+}
+
+impl<L: Link + ?Sized> Link for Box<L> {
+    fn send(&mut self, packet: &[u8]) {
+        (**self).send(packet)
+    }
+
+    fn recv(&mut self) -> Vec<Vec<u8>> {
+        (**self).recv()
+    }
+}
+
+/// A UDP socket talking to one peer.
+pub struct UdpLink {
+    socket: UdpSocket,
+    peer: SocketAddr,
+}
+
+impl UdpLink {
+    pub fn new(bind: SocketAddr, peer: SocketAddr) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind(bind)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self { socket, peer })
+    }
+
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+}
+
+impl Link for UdpLink {
+    fn send(&mut self, packet: &[u8]) {
+        // a lost packet is resent with the next one, errors are no worse than that
+        let _ = self.socket.send_to(packet, self.peer);
+    }
+
+    fn recv(&mut self) -> Vec<Vec<u8>> {
+        let mut ret = Vec::new();
+        let mut buf = [0u8; 1024];
+        loop {
+            match self.socket.recv_from(&mut buf) {
+                Ok((len, from)) if from == self.peer => ret.push(buf[..len].to_vec()),
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                // the peer isn't listening yet
+                Err(e) if e.kind() == ErrorKind::ConnectionRefused => {}
+                Err(e) => {
+                    eprintln!("net: {}", e);
+                    break;
+                }
+            }
+        }
+        ret
+    }
+}
+
+/// Wraps a link, dropping some outgoing packets and holding the rest back for a while.
+pub struct LossyLink<L> {
+    inner: L,
+    /// chance of dropping each packet, 0 to 1
+    pub loss: f32,
+    /// frames each packet is held back
+    pub delay: u32,
+    /// up to this many more frames, at random
+    pub jitter: u32,
+    rng: Rng,
+    frame: u64,
+    /// packets and the frame they go out on
+    queue: Vec<(u64, Vec<u8>)>,
+}
+
+impl<L: Link> LossyLink<L> {
+    pub fn new(inner: L, loss: f32, delay: u32, seed: u64) -> Self {
+        Self {
+            inner,
+            loss,
+            delay,
+            jitter: 0,
+            rng: Rng::new(seed),
+            frame: 0,
+            queue: Vec::new(),
+        }
+    }
+}
+
+impl<L: Link> Link for LossyLink<L> {
+    fn send(&mut self, packet: &[u8]) {
+        if self.rng.range(0.0, 1.0) < self.loss {
+            return;
+        }
+        let jitter = (self.rng.range(0.0, 1.0) * (self.jitter + 1) as f32) as u64;
+        let due = self.frame + self.delay as u64 + jitter.min(self.jitter as u64);
+        self.queue.push((due, packet.to_vec()));
+    }
+
+    fn recv(&mut self) -> Vec<Vec<u8>> {
+        self.frame += 1;
+        let frame = self.frame;
+        let inner = &mut self.inner;
+        self.queue.retain(|(due, packet)| {
+            if *due <= frame {
+                inner.send(packet);
+            }
+            *due > frame
+        });
+        self.inner.recv()
+    }
+}
+
+/// One peer's view of a networked game.
+pub struct Session<L> {
+    heli: Heli,
+    link: L,
+    /// index of our ship, the other peer flies the other one
+    local: usize,
+    /// frames simulated so far
+    frame: u32,
+    /// ours, by frame
+    local_inputs: Vec<Input>,
+    /// theirs by frame, as far as we have them without gaps
+    remote_inputs: Vec<Input>,
+    /// what we simulated the other ship with, by frame
+    used_remote: Vec<Input>,
+    /// how many of our inputs the peer has
+    acked: u32,
+    /// snapshots of the world before each frame not yet confirmed, oldest first
+    states: VecDeque<(u32, String)>,
+    /// `data_hash` of our data files, packets from a peer with different ones are dropped
+    data: u32,
+    /// whether the other peer's data files differ from ours
+    mismatch: bool,
+    /// frames simulated again after a misprediction, for the curious
+    pub rollbacks: u64,
+}
+
+impl<L: Link> Session<L> {
+    /// `local` is 0 if we fly player 1, 1 for player 2. Both peers must start from identical
+    /// worlds.
+    pub fn new(mut heli: Heli, link: L, local: usize) -> Self {
+        prepare(heli.world_mut());
+        Self {
+            heli,
+            link,
+            local,
+            frame: 0,
+            local_inputs: Vec::new(),
+            remote_inputs: Vec::new(),
+            used_remote: Vec::new(),
+            acked: 0,
+            states: VecDeque::new(),
+            data: data_hash(),
+            mismatch: false,
+            rollbacks: 0,
+        }
+    }
+
+    pub fn heli(&self) -> &Heli {
+        &self.heli
+    }
+
+    pub fn heli_mut(&mut self) -> &mut Heli {
+        &mut self.heli
+    }
+
+    /// Frames simulated so far.
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// Frames simulated with the other peer's real input.
+    pub fn confirmed(&self) -> u32 {
+        self.frame.min(self.remote_inputs.len() as u32)
+    }
+
+    /// Whether the other peer loaded different rules or spawners. Their inputs are ignored
+    /// then, so the game never gets going.
+    pub fn mismatch(&self) -> bool {
+        self.mismatch
+    }
+
+    /// Simulates one frame with `input` for our ship, unless we're too far ahead of the other
+    /// peer. Returns whether a frame was simulated.
+    pub fn advance(&mut self, input: Input) -> bool {
+        self.sync();
+        if self.frame - self.confirmed() >= MAX_PREDICTION {
+            self.send();
+            return false;
+        }
+
+        self.local_inputs.push(input);
+        let remote = self.predict(self.frame);
+        self.used_remote.push(remote);
+        self.states
+            .push_back((self.frame, snapshot::save(self.heli.world())));
+        self.simulate(self.frame);
+        self.frame += 1;
+        self.send();
+        self.forget();
+        true
+    }
+
+    /// Keeps talking to the other peer without simulating anything.
+    pub fn poll(&mut self) {
+        self.sync();
+        self.send();
+    }
+
+    /// Takes in whatever the other peer sent, rolling back if it differs from our guesses.
+    fn sync(&mut self) {
+        let old_confirmed = self.confirmed();
+        for packet in self.link.recv() {
+            self.receive(&packet);
+        }
+
+        let mispredicted = (old_confirmed..self.confirmed())
+            .find(|f| self.used_remote[*f as usize] != self.remote_inputs[*f as usize]);
+        if let Some(first) = mispredicted {
+            self.rollback(first);
+        }
+        self.forget();
+    }
+
+    /// Restores the state from before `from` and simulates up to the current frame again.
+    fn rollback(&mut self, from: u32) {
+        while self.states.front().is_some_and(|(f, _)| *f < from) {
+            self.states.pop_front();
+        }
+        let (saved, text) = self.states.pop_front().unwrap();
+        debug_assert_eq!(saved, from);
+        self.restore(&text);
+        self.states.clear();
+
+        for f in from..self.frame {
+            self.used_remote[f as usize] = self.predict(f);
+            self.states
+                .push_back((f, snapshot::save(self.heli.world())));
+            self.simulate(f);
+            self.rollbacks += 1;
+        }
+    }
+
+    fn simulate(&mut self, frame: u32) {
+        let mut inputs = [Input::default(); 2];
+        inputs[self.local] = self.local_inputs[frame as usize];
+        inputs[1 - self.local] = self.used_remote[frame as usize];
+        for (ship, input) in ships(self.heli.world()).into_iter().zip(inputs.iter()) {
+            *self.heli.world_mut().get_mut::<Input>(ship).unwrap() = *input;
+        }
+        self.heli.step(FIXED_STEP);
+    }
+
+    /// Their real input if we have it, otherwise whatever they sent last.
+    fn predict(&self, frame: u32) -> Input {
+        match self.remote_inputs.get(frame as usize) {
+            Some(input) => *input,
+            None => self.remote_inputs.last().copied().unwrap_or_default(),
+        }
+    }
+
+    /// Drops snapshots from before the latest confirmed frame, nothing can roll back past it.
+    fn forget(&mut self) {
+        let confirmed = self.confirmed();
+        while self.states.front().is_some_and(|(f, _)| *f < confirmed) {
+            self.states.pop_front();
+        }
+    }
+
+    /// Swaps in a saved world, keeping what snapshots leave out.
+    fn restore(&mut self, text: &str) {
+        let mut world = snapshot::load(text).expect("can't load own snapshot");
+        let old = self.heli.world_mut();
+
+        let registry = old
+            .query::<(&Registry,)>()
+            .iter()
+            .next()
+            .map(|(_, (r,))| r.clone());
+        world.spawn((registry.unwrap_or_default(),));
+
+        // particles and streaks are only for show, so they carry on from the newer world
+        let particles = old
+            .query::<(&Particles,)>()
+            .iter()
+            .next()
+            .map(|(ent, _)| ent);
+        let new_particles = world
+            .query::<(&Particles,)>()
+            .iter()
+            .next()
+            .map(|(ent, _)| ent);
+        if let (Some(from), Some(to)) = (particles, new_particles) {
+            let particles = old.remove_one::<Particles>(from).unwrap();
+            world.insert_one(to, particles).unwrap();
+        }
+        let zones: Vec<Entity> = world
+            .query::<hecs::Without<WindStreaks, (&WindZone,)>>()
+            .iter()
+            .map(|(ent, _)| ent)
+            .collect();
+        for ent in zones {
+            world.insert_one(ent, WindStreaks::default()).unwrap();
+        }
+
+        *old = world;
+    }
+
+    fn send(&mut self) {
+        let start = self.acked.min(self.frame);
+        let end = self.frame.min(start + MAX_INPUTS as u32);
+        let inputs: Vec<Input> = self.local_inputs[start as usize..end as usize].to_vec();
+        let packet = encode(self.data, self.remote_inputs.len() as u32, start, &inputs);
+        self.link.send(&packet);
+    }
+
+    fn receive(&mut self, packet: &[u8]) {
+        let (data, ack, start, inputs) = match decode(packet) {
+            Some(decoded) => decoded,
+            None => return,
+        };
+        if data != self.data {
+            self.mismatch = true;
+            return;
+        }
+        self.acked = self.acked.max(ack);
+        for (i, input) in inputs.into_iter().enumerate() {
+            let frame = start as usize + i;
+            if frame == self.remote_inputs.len() {
+                self.remote_inputs.push(input);
+            }
+        }
+    }
+}
+
+/// What `--net` on the command line asked for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetOptions {
+    pub bind: SocketAddr,
+    pub peer: SocketAddr,
+    /// 0 for player 1, 1 for player 2
+    pub player: usize,
+    pub loss: f32,
+    /// frames
+    pub delay: u32,
+}
+
+impl NetOptions {
+    /// None when there's no `--net`, which means a local game.
+    ///
+    /// `--net <bind address> <peer address> <1 or 2> [--loss <0 to 1>] [--delay <frames>]`
+    pub fn from_args(args: &[String]) -> Option<Result<Self, String>> {
+        let at = args.iter().position(|a| a == "--net")?;
+        Some(Self::parse(&args[at + 1..]))
+    }
+
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let usage = || {
+            "usage: --net <bind address> <peer address> <1 or 2> [--loss <0 to 1>] [--delay <frames>]"
+                .to_string()
+        };
+        if args.len() < 3 {
+            return Err(usage());
+        }
+        let address = |s: &str| s.parse::<SocketAddr>().map_err(|e| format!("{}: {}", s, e));
+        let mut ret = Self {
+            bind: address(&args[0])?,
+            peer: address(&args[1])?,
+            player: match args[2].as_str() {
+                "1" => 0,
+                "2" => 1,
+                _ => return Err(usage()),
+            },
+            loss: 0.0,
+            delay: 0,
+        };
+        let mut rest = args[3..].iter();
+        while let Some(flag) = rest.next() {
+            let value = rest.next().ok_or_else(usage)?;
+            match flag.as_str() {
+                "--loss" => ret.loss = value.parse().map_err(|_| usage())?,
+                "--delay" => ret.delay = value.parse().map_err(|_| usage())?,
+                _ => return Err(usage()),
+            }
+        }
+        Ok(ret)
+    }
+}
+
+/// A networked game in the window, played with the arrow keys.
+pub struct NetGame {
+    session: Session<Box<dyn Link>>,
+    controls: Controls,
+}
+
+impl NetGame {
+    pub fn new(options: &NetOptions) -> std::io::Result<Self> {
+        let udp = UdpLink::new(options.bind, options.peer)?;
+        let link: Box<dyn Link> = if options.loss > 0.0 || options.delay > 0 {
+            Box::new(LossyLink::new(
+                udp,
+                options.loss,
+                options.delay,
+                options.bind.port() as u64,
+            ))
+        } else {
+            Box::new(udp)
+        };
+        let heli = Heli::new(&Options {
+            mode: Mode::Arena,
+            opponent: Opponent::Human,
+            // both peers must fly the same classes, so network games use the default
+            ships: Vec::new(),
+        });
+        Ok(Self {
+            session: Session::new(heli, link, options.player),
+            controls: Controls::arrows(),
+        })
+    }
+
+    pub fn heli(&self) -> &Heli {
+        self.session.heli()
+    }
+
+    pub fn update(&mut self) {
+        let input = self.controls.read();
+        let steps = self.session.heli_mut().fixed_steps();
+        if steps == 0 {
+            // keep listening so a stalled peer catches up
+            self.session.poll();
+        }
+        for _ in 0..steps {
+            self.session.advance(input);
+        }
+        let heli = self.session.heli_mut();
+        heli.msc();
+        if is_key_down(KeyCode::Escape) || is_key_down(KeyCode::Q) {
+            heli.world_mut().spawn((Quit,));
+        }
+    }
+
+    pub fn draw(&self) {
+        self.session.heli().draw();
+        let session = &self.session;
+        let waiting = session.frame() - session.confirmed();
+        if session.mismatch() {
+            draw_text(
+                "the other player has different rules.txt or spawners.txt",
+                10.0,
+                60.0,
+                30.0,
+                WHITE,
+            );
+        } else if waiting >= MAX_PREDICTION {
+            draw_text("waiting for the other player", 10.0, 60.0, 30.0, WHITE);
+        }
+    }
+
+    pub fn ui(&mut self) {
+        self.session.heli_mut().ui();
+    }
+
+    pub fn should_quit(&self) -> bool {
+        self.session.heli().should_quit()
+    }
+}
+
+/// Makes a world ready for networked play: pilots are driven by the session rather than the
+/// keyboard, and level scripts are left out since their state can't be rolled back.
+fn prepare(w: &mut hecs::World) {
+    let unwanted: Vec<Entity> = w
+        .query::<(&Controls,)>()
+        .iter()
+        .map(|(ent, _)| ent)
+        .collect();
+    for ent in unwanted {
+        w.remove_one::<Controls>(ent).unwrap();
+    }
+    let scripts: Vec<Entity> = w.query::<(&Script,)>().iter().map(|(ent, _)| ent).collect();
+    for ent in scripts {
+        w.despawn(ent).unwrap();
+    }
+    // particles aren't rolled back, so they mustn't push anything
+    for (_, (settings,)) in w.query_mut::<(&mut Settings,)>() {
+        settings.downwash = 0.0;
+    }
+}
+
+/// `data` is the sender's `data_hash`, `ack` is how many of the receiver's inputs the sender
+/// has, then come the sender's inputs from frame `start` on.
+fn encode(data: u32, ack: u32, start: u32, inputs: &[Input]) -> Vec<u8> {
+    let mut ret = vec![MAGIC];
+    ret.extend_from_slice(&data.to_le_bytes());
+    ret.extend_from_slice(&ack.to_le_bytes());
+    ret.extend_from_slice(&start.to_le_bytes());
+    ret.push(inputs.len() as u8);
+    ret.extend(inputs.iter().map(|i| {
+        i.up as u8
+            | (i.down as u8) << 1
+            | (i.left as u8) << 2
+            | (i.right as u8) << 3
+            | (i.fire as u8) << 4
+    }));
+    ret
+}
+
+fn decode(packet: &[u8]) -> Option<(u32, u32, u32, Vec<Input>)> {
+    if packet.len() < 14 || packet[0] != MAGIC {
+        return None;
+    }
+    let word = |at: usize| {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(&packet[at..at + 4]);
+        u32::from_le_bytes(bytes)
+    };
+    let count = packet[13] as usize;
+    let bits = packet.get(14..14 + count)?;
+    let inputs = bits
+        .iter()
+        .map(|b| Input {
+            up: b & 1 != 0,
+            down: b & 2 != 0,
+            left: b & 4 != 0,
+            right: b & 8 != 0,
+            fire: b & 16 != 0,
+        })
+        .collect();
+    Some((word(1), word(5), word(9), inputs))
+}
+
+#[test]
+fn peers_agree_over_lossy_loopback() {
+    let any: SocketAddr = "127.0.0.1:0".parse().unwrap();
+    let mut a = UdpLink::new(any, any).unwrap();
+    let mut b = UdpLink::new(any, any).unwrap();
+    a.peer = b.local_addr().unwrap();
+    b.peer = a.local_addr().unwrap();
+
+    let options = Options {
+        mode: Mode::Arena,
+        opponent: Opponent::Human,
+        ships: Vec::new(),
+    };
+    let mut peers = [
+        Session::new(Heli::new(&options), LossyLink::new(a, 0.3, 4, 1), 0),
+        Session::new(Heli::new(&options), LossyLink::new(b, 0.3, 2, 2), 1),
+    ];
+    peers[1].link.jitter = 3;
+
+    // each pilot changes what they hold every so often, at different rates
+    let pilot = |seed: u32, frame: u32| {
+        let bits = (frame / (7 + seed * 5)).wrapping_mul(2_654_435_761) >> 28;
+        Input {
+            up: bits & 1 != 0,
+            down: false,
+            left: bits & 2 != 0,
+            right: bits & 4 != 0,
+            fire: bits & 8 != 0,
+        }
+    };
+    let frames = 240;
+    for _ in 0..10_000 {
+        if peers
+            .iter()
+            .all(|p| p.frame() == frames && p.confirmed() == frames)
+        {
+            break;
+        }
+        for (seed, peer) in peers.iter_mut().enumerate() {
+            if peer.frame() < frames {
+                peer.advance(pilot(seed as u32, peer.frame()));
+            } else {
+                // still resending until the other side has everything
+                peer.poll();
+            }
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    assert!(peers.iter().all(|p| p.confirmed() == frames));
+    assert!(peers.iter().any(|p| p.rollbacks > 0));
+
+    // entity ids differ once a peer has rolled back, everything else must match
+    let state = |p: &Session<LossyLink<UdpLink>>| {
+        let mut lines: Vec<String> = snapshot::save(p.heli().world())
+            .lines()
+            .map(|l| l.splitn(2, ' ').last().unwrap().to_string())
+            .collect();
+        lines.sort();
+        lines
+    };
+    assert_eq!(state(&peers[0]), state(&peers[1]));
+}
+
+#[test]
+fn peers_with_different_data_never_start() {
+    let any: SocketAddr = "127.0.0.1:0".parse().unwrap();
+    let mut a = UdpLink::new(any, any).unwrap();
+    let mut b = UdpLink::new(any, any).unwrap();
+    a.peer = b.local_addr().unwrap();
+    b.peer = a.local_addr().unwrap();
+
+    let options = Options {
+        mode: Mode::Arena,
+        opponent: Opponent::Human,
+        ships: Vec::new(),
+    };
+    let mut peers = [
+        Session::new(Heli::new(&options), a, 0),
+        Session::new(Heli::new(&options), b, 1),
+    ];
+    peers[1].data ^= 1;
+
+    for _ in 0..MAX_PREDICTION * 2 {
+        for peer in peers.iter_mut() {
+            peer.advance(Input::default());
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    assert!(peers.iter().all(|p| p.mismatch() && p.confirmed() == 0));
+    assert!(peers.iter().all(|p| p.frame() == MAX_PREDICTION));
+}
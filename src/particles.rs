@@ -0,0 +1,499 @@
+use crate::constants::{mass_of, Settings, TRASH_WIREFRAME};
+use crate::kinematic::Kinematic;
+use crate::spawner::Registry;
+use crate::system::{draw_wireframe, Boost, Collides, Contacts, Pos, Rot, Time, Vel};
+use crate::util::Rng;
+use crate::wind::Winds;
+use alloc::borrow::Cow;
+use alloc::sync::Arc;
+use hecs::Entity;
+use macroquad::prelude::*;
+
+/// Emits particles into the `Particles` pool from the `EmitterDef` with this id in the
+/// `Registry`. Ids that aren't registered emit nothing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Emitter {
+    pub id: Cow<'static, str>,
+    /// particles owed but not yet emitted
+    pending: f32,
+}
+
+/// How an `Emitter` emits. Particles aren't entities, they can't be queried and don't collide,
+/// which keeps hundreds of them cheap. Particles that `push` can shove bodies they fly into
+/// when `Settings::downwash` is on, see `Particles::downwash`.
+#[derive(Debug, Clone)]
+pub struct EmitterDef {
+    pub trigger: Trigger,
+    /// particles per second, for triggers that emit continuously
+    pub rate: f32,
+    /// particles per burst, for triggers that emit in bursts
+    pub burst: u32,
+    /// local space, where particles appear
+    pub offset: Vec2,
+    /// radians from local up, the direction particles leave in
+    pub direction: f32,
+    /// radians, particles leave up to this far either side of direction
+    pub spread: f32,
+    /// meters per second, (min, max)
+    pub speed: (f32, f32),
+    /// seconds, (min, max)
+    pub lifetime: (f32, f32),
+    /// radians per second
+    pub spin: f32,
+    /// (fraction of life, color) sorted by fraction, colors in between are blended
+    /// empty means use the emitting entity's color
+    pub gradient: Arc<[(f32, Color)]>,
+    pub shape: &'static [(f32, f32)],
+    /// add the emitter's velocity to each particle
+    pub inherit_velocity: bool,
+    pub grav: bool,
+    pub drag: bool,
+    /// meters per second given to a body of mass 1 each particle hits, 0 for particles that
+    /// pass through everything
+    pub push: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Trigger {
+    /// while `Boost` is nonzero, reversed when boost is negative
+    Boost,
+    /// a burst every time the entity bounces off something
+    Collision,
+    /// always
+    Continuous,
+    /// a burst when a `Mortal` entity expires
+    Death,
+}
+
+/// Every live particle in the world.
+#[derive(Debug)]
+pub struct Particles {
+    list: Vec<Particle>,
+    rng: Rng,
+}
+
+#[derive(Debug)]
+struct Particle {
+    pos: Vec2,
+    vel: Vec2,
+    rot: f32,
+    spin: f32,
+    /// seconds
+    age: f32,
+    /// seconds
+    life: f32,
+    grav: bool,
+    drag: bool,
+    /// meters per second, set to 0 once spent
+    push: f32,
+    /// the emitting entity, never pushed by its own particles
+    source: Option<Entity>,
+    shape: &'static [(f32, f32)],
+    /// used when gradient is empty
+    color: Color,
+    gradient: Arc<[(f32, Color)]>,
+}
+
+impl Emitter {
+    pub fn new(id: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            id: id.into(),
+            pending: 0.0,
+        }
+    }
+
+    pub fn system(w: &mut hecs::World) {
+        let delta_t = Time::get(w).delta;
+        let contacts: Vec<(Entity, Vec2, Vec2)> = w
+            .query::<(&Contacts,)>()
+            .iter()
+            .flat_map(|(_, (c,))| c.0.iter().map(|c| (c.a, c.point, c.normal)))
+            .collect();
+
+        let mut emitted: Vec<Particle> = Vec::new();
+        let mut rng = match Particles::rng(w) {
+            Some(rng) => rng,
+            None => return,
+        };
+
+        Registry::with(w, |registry| {
+            for (ent, (emitter, pos, rot, vel, boost, color)) in w
+                .query::<(
+                    &mut Emitter,
+                    &Pos,
+                    Option<&Rot>,
+                    Option<&Vel>,
+                    Option<&Boost>,
+                    Option<&Color>,
+                )>()
+                .iter()
+            {
+                let def = match registry.emitters.get(emitter.id.as_ref()) {
+                    Some(def) => def,
+                    None => continue,
+                };
+                let source = Source {
+                    ent,
+                    pos: pos.0,
+                    rot: rot.map(|r| r.0).unwrap_or(0.0),
+                    vel: vel.map(|v| v.0).unwrap_or(vec2(0.0, 0.0)),
+                    color: color.cloned().unwrap_or(BLACK),
+                };
+
+                match def.trigger {
+                    Trigger::Boost | Trigger::Continuous => {
+                        let sign = match def.trigger {
+                            Trigger::Boost => boost.map(|b| b.0).unwrap_or(0.0),
+                            _ => 1.0,
+                        };
+                        if sign.abs() <= 0.00001 {
+                            emitter.pending = 0.0;
+                            continue;
+                        }
+                        emitter.pending += def.rate * delta_t;
+                        while emitter.pending >= 1.0 {
+                            emitter.pending -= 1.0;
+                            emitted.push(def.particle(&source, sign.signum(), None, &mut rng));
+                        }
+                    }
+                    Trigger::Collision => {
+                        for (_, point, normal) in contacts.iter().filter(|c| c.0 == ent) {
+                            // fly back out of whatever we hit
+                            let away = (-normal.x).atan2(-normal.y);
+                            let hit = Source {
+                                pos: *point,
+                                ..source.clone()
+                            };
+                            for _ in 0..def.burst {
+                                emitted.push(def.particle(&hit, 1.0, Some(away), &mut rng));
+                            }
+                        }
+                    }
+                    Trigger::Death => {}
+                }
+            }
+        });
+
+        Particles::extend(w, emitted, rng);
+    }
+
+    /// Emits death bursts for entities about to be despawned.
+    pub fn on_death(w: &mut hecs::World, dying: &[Entity]) {
+        let mut emitted: Vec<Particle> = Vec::new();
+        let mut rng = match Particles::rng(w) {
+            Some(rng) => rng,
+            None => return,
+        };
+
+        Registry::with(w, |registry| {
+            for ent in dying {
+                let mut query = match w
+                    .query_one::<(&Emitter, &Pos, Option<&Rot>, Option<&Vel>, Option<&Color>)>(*ent)
+                {
+                    Ok(query) => query,
+                    Err(_) => continue,
+                };
+                if let Some((emitter, pos, rot, vel, color)) = query.get() {
+                    let def = match registry.emitters.get(emitter.id.as_ref()) {
+                        Some(def) if def.trigger == Trigger::Death => def,
+                        _ => continue,
+                    };
+                    let source = Source {
+                        ent: *ent,
+                        pos: pos.0,
+                        rot: rot.map(|r| r.0).unwrap_or(0.0),
+                        vel: vel.map(|v| v.0).unwrap_or(vec2(0.0, 0.0)),
+                        color: color.cloned().unwrap_or(BLACK),
+                    };
+                    for _ in 0..def.burst {
+                        emitted.push(def.particle(&source, 1.0, None, &mut rng));
+                    }
+                }
+            }
+        });
+
+        Particles::extend(w, emitted, rng);
+    }
+}
+
+impl EmitterDef {
+    pub fn new(trigger: Trigger) -> Self {
+        Self {
+            trigger,
+            rate: 30.0,
+            burst: 10,
+            offset: vec2(0.0, 0.0),
+            direction: 0.0,
+            spread: core::f32::consts::PI,
+            speed: (10.0, 50.0),
+            lifetime: (0.5, 1.0),
+            spin: 0.0,
+            gradient: Arc::from(Vec::new()),
+            shape: TRASH_WIREFRAME,
+            inherit_velocity: true,
+            grav: true,
+            drag: true,
+            push: 0.0,
+        }
+    }
+
+    /// Exhaust squares shot out the bottom of a boosting ship.
+    pub fn firetrail() -> Self {
+        Self {
+            rate: 60.0,
+            offset: vec2(0.0, -10.0),
+            direction: core::f32::consts::PI,
+            spread: 0.0,
+            speed: (200.0, 200.0),
+            lifetime: (2.0, 2.0),
+            spin: 15.0,
+            push: 0.25,
+            ..Self::new(Trigger::Boost)
+        }
+    }
+
+    /// Grey puffs drifting up out of the ground, placed in levels.
+    pub fn vent() -> Self {
+        Self {
+            rate: 8.0,
+            spread: 0.3,
+            speed: (20.0, 40.0),
+            lifetime: (2.0, 3.0),
+            spin: 1.0,
+            gradient: Arc::from(vec![
+                (0.0, Color::new(0.6, 0.6, 0.6, 0.8)),
+                (1.0, Color::new(0.6, 0.6, 0.6, 0.0)),
+            ]),
+            inherit_velocity: false,
+            grav: false,
+            ..Self::new(Trigger::Continuous)
+        }
+    }
+
+    /// `sign` flips the emitter, `direction` overrides the emitter's direction in world space.
+    fn particle(&self, source: &Source, sign: f32, direction: Option<f32>, rng: &mut Rng) -> Particle {
+        let q = Rot(source.rot).quat();
+        let rotate = |v: Vec2| q.mul_vec3(v.extend(0.)).truncate();
+
+        let angle = direction.unwrap_or(source.rot + self.direction)
+            + rng.range(-self.spread, self.spread);
+        let speed = rng.range(self.speed.0, self.speed.1);
+        let mut vel = vec2(-angle.sin(), angle.cos()) * speed * sign;
+        if self.inherit_velocity {
+            vel += source.vel;
+        }
+
+        Particle {
+            pos: source.pos + rotate(self.offset) * sign,
+            vel,
+            rot: source.rot,
+            spin: self.spin * sign,
+            age: 0.0,
+            life: rng.range(self.lifetime.0, self.lifetime.1),
+            grav: self.grav,
+            drag: self.drag,
+            push: self.push,
+            source: Some(source.ent),
+            shape: self.shape,
+            color: source.color,
+            gradient: self.gradient.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Source {
+    ent: Entity,
+    pos: Vec2,
+    rot: f32,
+    vel: Vec2,
+    color: Color,
+}
+
+/// fraction of its speed, relative to what it hit, a spent particle keeps
+const SPLASH: f32 = 0.2;
+
+impl Default for Particles {
+    fn default() -> Self {
+        Self {
+            list: Vec::new(),
+            rng: Rng::new(0),
+        }
+    }
+}
+
+impl Particles {
+    /// Moves and expires particles.
+    pub fn system(w: &mut hecs::World) {
+        let Time { now: time, delta: delta_t } = Time::get(w);
+        let settings = Settings::get(w);
+        let winds = Winds::collect(w);
+        let drag_mult = delta_t * settings.drag_coefficient;
+
+        for (_id, (particles,)) in w.query_mut::<(&mut Particles,)>() {
+            for p in particles.list.iter_mut() {
+                p.age += delta_t;
+                p.pos += p.vel * delta_t;
+                p.rot += p.spin * delta_t;
+                if p.grav {
+                    p.vel.y += settings.gravity * delta_t;
+                }
+                if p.drag {
+                    p.vel -= (p.vel - winds.air_velocity(p.pos, time)) * drag_mult;
+                    p.spin -= p.spin * delta_t * settings.rotational_drag_coefficient;
+                }
+            }
+            particles.list.retain(|p| p.age < p.life);
+        }
+    }
+
+    /// Pushes bodies that pushing particles fly into, such as a ship hovering over another.
+    /// Bodies are tested as circles around their shapes, rough but cheap enough for hundreds of
+    /// particles. Each particle pushes once, then splashes off.
+    pub fn downwash(w: &mut hecs::World) {
+        let strength = Settings::get(w).downwash;
+        if strength <= 0.0 {
+            return;
+        }
+        // (entity, center, radius squared, velocity)
+        let bodies: Vec<(Entity, Vec2, f32, Vec2)> = w
+            .query::<hecs::Without<Kinematic, (&Collides, &Pos, &Vel)>>()
+            .iter()
+            .map(|(ent, (Collides(shape), pos, vel))| {
+                let sphere = shape.compute_local_bounding_sphere();
+                let center = pos.0 + vec2(sphere.center().x, sphere.center().y);
+                (ent, center, sphere.radius().powi(2), vel.0)
+            })
+            .collect();
+        if bodies.is_empty() {
+            return;
+        }
+
+        let mut pushes: Vec<(Entity, Vec2)> = Vec::new();
+        for (_id, (particles,)) in w.query_mut::<(&mut Particles,)>() {
+            for p in particles.list.iter_mut().filter(|p| p.push > 0.0) {
+                let hit = bodies.iter().find(|(ent, center, radius_sq, _)| {
+                    Some(*ent) != p.source && (p.pos - *center).length_squared() < *radius_sq
+                });
+                if let Some((ent, _, _, vel)) = hit {
+                    let relative = p.vel - *vel;
+                    let direction = relative / relative.length().max(0.0001);
+                    pushes.push((*ent, direction * p.push * strength));
+                    p.push = 0.0;
+                    p.vel = *vel - relative * SPLASH;
+                }
+            }
+        }
+
+        for (ent, push) in pushes {
+            let mass = mass_of(w, ent);
+            if let Ok(mut vel) = w.get_mut::<Vel>(ent) {
+                vel.0 += push / mass;
+            }
+        }
+    }
+
+    /// Expects the world camera to be set.
+    pub fn draw(w: &hecs::World) {
+        for (_id, (particles,)) in w.query::<(&Particles,)>().iter() {
+            for p in &particles.list {
+                let color = p.color_at(p.age / p.life);
+                draw_wireframe(p.shape, p.pos, Rot(p.rot).quat(), vec2(1.0, 1.0), 1.0, color);
+            }
+        }
+    }
+
+    pub fn len(w: &hecs::World) -> usize {
+        w.query::<(&Particles,)>()
+            .iter()
+            .map(|(_, (p,))| p.list.len())
+            .sum()
+    }
+
+    /// The pool's random number generator, taken out so emitters can use it while borrowing the
+    /// world. Put it back with `extend`.
+    fn rng(w: &mut hecs::World) -> Option<Rng> {
+        w.query_mut::<(&mut Particles,)>()
+            .into_iter()
+            .next()
+            .map(|(_, (p,))| p.rng.clone())
+    }
+
+    fn extend(w: &mut hecs::World, emitted: Vec<Particle>, rng: Rng) {
+        if let Some((_, (particles,))) = w.query_mut::<(&mut Particles,)>().into_iter().next() {
+            particles.list.extend(emitted);
+            particles.rng = rng;
+        }
+    }
+}
+
+impl Particle {
+    fn color_at(&self, life: f32) -> Color {
+        let g = &self.gradient;
+        if g.is_empty() {
+            return self.color;
+        }
+        if life <= g[0].0 {
+            return g[0].1;
+        }
+        for (a, b) in g.iter().zip(g.iter().skip(1)) {
+            if life <= b.0 {
+                let t = (life - a.0) / (b.0 - a.0).max(0.0001);
+                return Color::new(
+                    a.1.r + (b.1.r - a.1.r) * t,
+                    a.1.g + (b.1.g - a.1.g) * t,
+                    a.1.b + (b.1.b - a.1.b) * t,
+                    a.1.a + (b.1.a - a.1.a) * t,
+                );
+            }
+        }
+        g[g.len() - 1].1
+    }
+}
+
+#[test]
+fn downwash_pushes_ship_below() {
+    use crate::system::spawn_ship;
+
+    let mut w = hecs::World::new();
+    w.spawn((Settings {
+        downwash: 1.0,
+        ..Settings::default()
+    },));
+    let ship = spawn_ship(&mut w, vec2(0.0, 0.0), WHITE);
+    let above = spawn_ship(&mut w, vec2(0.0, 20.0), WHITE);
+    // exhaust from the ship above, starting inside the ship below
+    let exhaust = |source: Entity| {
+        let mut p = EmitterDef::firetrail().particle(
+            &Source {
+                ent: source,
+                pos: vec2(0.0, 10.0),
+                rot: 0.0,
+                vel: vec2(0.0, 0.0),
+                color: WHITE,
+            },
+            1.0,
+            None,
+            &mut Rng::new(0),
+        );
+        p.pos = vec2(0.0, 0.0);
+        p
+    };
+    w.spawn((Particles {
+        list: vec![exhaust(ship)],
+        rng: Rng::new(0),
+    },));
+
+    // a ship's own exhaust doesn't push it
+    Particles::downwash(&mut w);
+    assert_eq!(w.get::<Vel>(ship).unwrap().0, vec2(0.0, 0.0));
+
+    for (_, (particles,)) in w.query_mut::<(&mut Particles,)>() {
+        particles.list = vec![exhaust(above)];
+    }
+    Particles::downwash(&mut w);
+    assert!(w.get::<Vel>(ship).unwrap().0.y < 0.0);
+    for (_, (particles,)) in w.query::<(&Particles,)>().iter() {
+        assert_eq!(particles.list[0].push, 0.0);
+    }
+}
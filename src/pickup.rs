@@ -0,0 +1,329 @@
+//! Collectible pickups and the timed power-ups they give. Power-ups change `Settings` for the
+//! ship holding them only, see `PowerUps::apply`.
+
+use crate::commands::Commands;
+use crate::constants::{Settings, PICKUP_WIREFRAME, PLAYER_SIZE, WORLD_HEIGHT};
+use crate::system::{ships, Pos, Rot, RotVel, Time, Wireframe};
+use crate::util::Rng;
+use crate::weapon::Health;
+use hecs::Entity;
+use macroquad::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PickupKind {
+    /// fills the tank
+    Fuel,
+    /// restores all health
+    Repair,
+    /// stronger boost for a while
+    Boost,
+    /// projectiles do no damage for a while
+    Shield,
+    /// falls up for a while
+    Gravity,
+}
+
+/// Collected by the first ship to fly over it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pickup(pub PickupKind);
+
+/// Seconds of boost left. Ships without `Fuel` never run out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fuel {
+    pub amount: f32,
+    pub max: f32,
+}
+
+/// (kind, world time it wears off) for each timed power-up a ship holds.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PowerUps(pub Vec<(PickupKind, f64)>);
+
+/// Drops random pickups inside a rectangle every so often.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PickupDrops {
+    pub center: Vec2,
+    /// meters from the center to the edges
+    pub half_size: Vec2,
+    /// seconds between drops
+    pub interval: f32,
+    /// no drops while this many pickups are lying around
+    pub max: usize,
+    /// world time of the next drop
+    pub next_at: f64,
+    /// each drop is placed from the seed and the drop count, so replays and network peers agree
+    pub seed: u64,
+    pub dropped: u32,
+}
+
+pub const KINDS: &[PickupKind] = &[
+    PickupKind::Fuel,
+    PickupKind::Repair,
+    PickupKind::Boost,
+    PickupKind::Shield,
+    PickupKind::Gravity,
+];
+
+/// meters, ships closer than this plus their own size collect pickups
+const PICKUP_RADIUS: f32 = 6.0;
+
+/// boost power is multiplied by this while `PickupKind::Boost` is active
+const BOOST_MULTIPLIER: f32 = 1.6;
+
+/// seconds of fuel regained per second while not boosting
+const FUEL_REGEN: f32 = 0.5;
+
+impl PickupKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            PickupKind::Fuel => "fuel",
+            PickupKind::Repair => "repair",
+            PickupKind::Boost => "boost",
+            PickupKind::Shield => "shield",
+            PickupKind::Gravity => "gravity",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        KINDS.iter().cloned().find(|kind| kind.name() == name)
+    }
+
+    /// seconds the power-up lasts, 0 for pickups that act once
+    pub fn duration(self) -> f32 {
+        match self {
+            PickupKind::Fuel | PickupKind::Repair => 0.0,
+            PickupKind::Boost => 8.0,
+            PickupKind::Shield => 8.0,
+            PickupKind::Gravity => 6.0,
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            PickupKind::Fuel => ORANGE,
+            PickupKind::Repair => GREEN,
+            PickupKind::Boost => YELLOW,
+            PickupKind::Shield => SKYBLUE,
+            PickupKind::Gravity => PURPLE,
+        }
+    }
+}
+
+impl Pickup {
+    /// Spins in place until collected.
+    pub fn spawn(w: &mut hecs::World, pos: Vec2, kind: PickupKind) -> Entity {
+        w.spawn((
+            Pos(pos),
+            Rot(0.0),
+            RotVel(1.0),
+            Wireframe(PICKUP_WIREFRAME.into()),
+            kind.color(),
+            Pickup(kind),
+        ))
+    }
+
+    /// Drops, collects and expires pickups and power-ups.
+    pub fn system(w: &mut hecs::World) {
+        let now = Time::get(w).now;
+        for (_, (power_ups,)) in w.query_mut::<(&mut PowerUps,)>() {
+            power_ups.0.retain(|(_, until)| *until > now);
+        }
+
+        let lying = w.query::<(&Pickup,)>().iter().count();
+        let mut drops: Vec<(Vec2, PickupKind)> = Vec::new();
+        for (_, (d,)) in w.query_mut::<(&mut PickupDrops,)>() {
+            if now < d.next_at || lying + drops.len() >= d.max {
+                continue;
+            }
+            let mut rng = Rng::new(d.seed.wrapping_add(d.dropped as u64));
+            let offset = vec2(
+                rng.range(-d.half_size.x, d.half_size.x),
+                rng.range(-d.half_size.y, d.half_size.y),
+            );
+            let kind = KINDS[(rng.next_u64() % KINDS.len() as u64) as usize];
+            drops.push((d.center + offset, kind));
+            d.next_at = now + d.interval as f64;
+            d.dropped += 1;
+        }
+        for (pos, kind) in drops {
+            Self::spawn(w, pos, kind);
+        }
+
+        let ships: Vec<(Entity, Vec2)> = ships(w)
+            .into_iter()
+            .filter_map(|ent| w.get::<Pos>(ent).ok().map(|pos| (ent, pos.0)))
+            .collect();
+        let reach = PICKUP_RADIUS + PLAYER_SIZE * 0.5;
+        let collected: Vec<(Entity, Entity, PickupKind)> = w
+            .query::<(&Pickup, &Pos)>()
+            .iter()
+            .filter_map(|(ent, (pickup, pos))| {
+                ships
+                    .iter()
+                    .find(|(_, ship)| (*ship - pos.0).length() < reach)
+                    .map(|(ship, _)| (ent, *ship, pickup.0))
+            })
+            .collect();
+
+        let mut commands = Commands::default();
+        for (pickup, ship, kind) in collected {
+            collect(w, ship, kind, now);
+            commands.despawn(pickup);
+        }
+        commands.submit(w);
+    }
+}
+
+/// Gives `kind` to `ship`. Collecting a power-up it already holds starts its timer over.
+pub fn collect(w: &mut hecs::World, ship: Entity, kind: PickupKind, now: f64) {
+    match kind {
+        PickupKind::Fuel => {
+            if let Ok(mut fuel) = w.get_mut::<Fuel>(ship) {
+                fuel.amount = fuel.max;
+            }
+        }
+        PickupKind::Repair => {
+            if let Ok(mut health) = w.get_mut::<Health>(ship) {
+                health.hp = health.max;
+            }
+        }
+        timed => {
+            let until = now + timed.duration() as f64;
+            if let Ok(mut power_ups) = w.get_mut::<PowerUps>(ship) {
+                power_ups.0.retain(|(k, _)| *k != timed);
+                power_ups.0.push((timed, until));
+                return;
+            }
+            let _ = w.insert_one(ship, PowerUps(vec![(timed, until)]));
+        }
+    }
+}
+
+impl Fuel {
+    pub fn new(max: f32) -> Self {
+        Self { amount: max, max }
+    }
+
+    /// Burns fuel while `boost` is nonzero and refills slowly otherwise. Returns the boost
+    /// actually available, 0 once the tank is empty.
+    pub fn burn(&mut self, boost: f32, delta_t: f32) -> f32 {
+        if boost == 0.0 {
+            self.amount = (self.amount + FUEL_REGEN * delta_t).min(self.max);
+            return 0.0;
+        }
+        if self.amount <= 0.0 {
+            return 0.0;
+        }
+        self.amount = (self.amount - delta_t).max(0.0);
+        boost
+    }
+}
+
+impl PowerUps {
+    pub fn has(&self, kind: PickupKind) -> bool {
+        self.0.iter().any(|(k, _)| *k == kind)
+    }
+
+    /// `settings` as seen by the ship holding these.
+    pub fn apply(&self, settings: &Settings) -> Settings {
+        let mut ret = settings.clone();
+        if self.has(PickupKind::Boost) {
+            ret.boost_power *= BOOST_MULTIPLIER;
+        }
+        if self.has(PickupKind::Gravity) {
+            ret.gravity = -ret.gravity;
+        }
+        ret
+    }
+
+    /// Rings shielded ships. Expects the world camera to be set.
+    pub fn draw_world(w: &hecs::World) {
+        let meters_per_screen = 1.0 / WORLD_HEIGHT;
+        for (_, (power_ups, pos)) in w.query::<(&PowerUps, &Pos)>().iter() {
+            if power_ups.has(PickupKind::Shield) {
+                let p = pos.0 * meters_per_screen;
+                draw_circle_lines(
+                    p.x,
+                    p.y,
+                    PLAYER_SIZE * meters_per_screen,
+                    0.5 * meters_per_screen,
+                    PickupKind::Shield.color(),
+                );
+            }
+        }
+    }
+
+    /// Fuel and power-ups with the seconds they have left, a line per ship in the top right.
+    /// Expects the default camera to be set.
+    pub fn draw_hud(w: &hecs::World) {
+        let now = Time::get(w).now;
+        let mut y = 80.0;
+        for (i, ship) in ships(w).into_iter().enumerate() {
+            let mut line = format!("p{}", i + 1);
+            if let Ok(fuel) = w.get::<Fuel>(ship) {
+                line.push_str(&format!("  fuel {:.0}%", 100.0 * fuel.amount / fuel.max));
+            }
+            if let Ok(power_ups) = w.get::<PowerUps>(ship) {
+                for (kind, until) in &power_ups.0 {
+                    line.push_str(&format!("  {} {:.1}", kind.name(), until - now));
+                }
+            }
+            let color = w.get::<Color>(ship).map(|c| *c).unwrap_or(WHITE);
+            draw_text(&line, screen_width() - 420.0, y, 30.0, color);
+            y += 30.0;
+        }
+    }
+}
+
+#[test]
+fn pickups_are_collected_and_wear_off() {
+    use crate::system::spawn_ship;
+
+    let mut w = hecs::World::new();
+    w.spawn((Time {
+        now: 0.0,
+        delta: 0.1,
+    },));
+    let ship = spawn_ship(&mut w, vec2(0.0, 0.0), WHITE);
+    let far = Pickup::spawn(&mut w, vec2(100.0, 0.0), PickupKind::Shield);
+    let near = Pickup::spawn(&mut w, vec2(5.0, 0.0), PickupKind::Gravity);
+
+    Pickup::system(&mut w);
+    Commands::apply(&mut w);
+    assert!(w.contains(far) && !w.contains(near));
+    let settings = w.get::<PowerUps>(ship).unwrap().apply(&Settings::default());
+    assert_eq!(settings.gravity, -Settings::default().gravity);
+
+    for (_, (time,)) in w.query_mut::<(&mut Time,)>() {
+        time.now = 10.0;
+    }
+    Pickup::system(&mut w);
+    assert!(w.get::<PowerUps>(ship).unwrap().0.is_empty());
+}
+
+#[test]
+fn empty_tank_stops_hover_assist() {
+    use crate::assist::Assist;
+    use crate::system::{spawn_ship, Boost, Heli, Vel, FIXED_STEP};
+
+    let mut w = hecs::World::new();
+    w.spawn((Time::default(),));
+    w.spawn((Settings::default(),));
+    let ship = spawn_ship(&mut w, vec2(0.0, 0.0), WHITE);
+    w.insert(
+        ship,
+        (
+            Assist::HoverHold,
+            Fuel {
+                amount: 0.0,
+                max: 20.0,
+            },
+        ),
+    )
+    .unwrap();
+    let mut heli = Heli::from_world(w);
+
+    heli.step(FIXED_STEP);
+    let w = heli.world();
+    assert_eq!(w.get::<Boost>(ship).unwrap().0, 0.0);
+    assert!(w.get::<Vel>(ship).unwrap().0.y < 0.0);
+}
@@ -0,0 +1,306 @@
+use crate::constants::{Overrides, Settings};
+use crate::pickup::PowerUps;
+use crate::system::{Input, Pos, Rot, RotVel, Time, Vel};
+use core::f32::consts::{PI, TAU};
+use macroquad::prelude::*;
+
+/// A computer pilot. Bots only get to press the same buttons a human would.
+pub struct Bot(pub Box<dyn Pilot>);
+
+/// Decides which buttons to press based on what a player could see on screen.
+pub trait Pilot: Send + Sync {
+    fn fly(&mut self, view: &View) -> Input;
+}
+
+/// Everything a pilot gets to know about the world.
+#[derive(Debug, Clone)]
+pub struct View {
+    pub pos: Vec2,
+    pub vel: Vec2,
+    pub rot: f32,
+    pub rot_vel: f32,
+    /// from the settings window
+    pub gravity: f32,
+    /// from the settings window
+    pub boost_power: f32,
+    /// seconds since the last frame
+    pub delta_t: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum BotKind {
+    Hover,
+    Waypoints,
+    Race,
+}
+
+/// Points for bots to fly through.
+#[derive(Debug, Clone)]
+pub struct Course {
+    pub points: Vec<Vec2>,
+    /// whether to start over after the last point
+    pub looped: bool,
+}
+
+impl Bot {
+    pub fn new(kind: BotKind, course: &Course) -> Self {
+        match kind {
+            BotKind::Hover => Bot(Box::new(HoverPid::default())),
+            BotKind::Waypoints => Bot(Box::new(WaypointFollower::new(course.clone()))),
+            BotKind::Race => Bot(Box::new(RaceLine::new(course.clone()))),
+        }
+    }
+
+    pub fn system(w: &mut hecs::World) {
+        let delta_t = Time::get(w).delta;
+        let global = Settings::get(w);
+
+        for (_id, (bot, input, pos, vel, rot, rot_vel, overrides, power_ups)) in w.query_mut::<(
+            &mut Bot,
+            &mut Input,
+            &Pos,
+            &Vel,
+            &Rot,
+            &RotVel,
+            Option<&Overrides>,
+            Option<&PowerUps>,
+        )>() {
+            let settings = global.local(overrides, power_ups);
+            let view = View {
+                pos: pos.0,
+                vel: vel.0,
+                rot: rot.0,
+                rot_vel: rot_vel.0,
+                gravity: settings.gravity,
+                boost_power: settings.boost_power,
+                delta_t,
+            };
+            *input = bot.0.fly(&view);
+        }
+    }
+}
+
+/// Turns a desired acceleration into button presses.
+#[derive(Debug, Default, Clone)]
+struct Steer {
+    /// boost is all or nothing, this accumulates partial boosts between frames
+    throttle: f32,
+}
+
+impl Steer {
+    fn toward(&mut self, view: &View, accel: Vec2) -> Input {
+        let mut input = Input::default();
+
+        // boost has to cancel gravity on top of whatever we want
+        let thrust = accel - vec2(0.0, view.gravity);
+        // the ship boosts along its local y axis
+        let want = (-thrust.x).atan2(thrust.y);
+        let error = wrap_angle(want - view.rot);
+
+        let desired_rot_vel = error * 4.0;
+        if view.rot_vel < desired_rot_vel - 0.2 {
+            input.left = true;
+        } else if view.rot_vel > desired_rot_vel + 0.2 {
+            input.right = true;
+        }
+
+        let heading = vec2(-view.rot.sin(), view.rot.cos());
+        let along = thrust.dot(heading);
+        if error.abs() < 0.8 && along > 0.0 {
+            self.throttle += (along / view.boost_power).min(1.0);
+            if self.throttle >= 1.0 {
+                self.throttle -= 1.0;
+                input.up = true;
+            }
+        }
+
+        input
+    }
+}
+
+/// Holds position with a PID loop on position error.
+#[derive(Debug, Clone)]
+pub struct HoverPid {
+    /// where to hover, defaults to wherever the ship is when the bot first flies
+    pub target: Option<Vec2>,
+    pub kp: f32,
+    pub ki: f32,
+    pub kd: f32,
+    integral: Vec2,
+    steer: Steer,
+}
+
+impl Default for HoverPid {
+    fn default() -> Self {
+        Self {
+            target: None,
+            kp: 0.8,
+            ki: 0.05,
+            kd: 1.5,
+            integral: vec2(0.0, 0.0),
+            steer: Steer::default(),
+        }
+    }
+}
+
+impl HoverPid {
+    fn retarget(&mut self, target: Vec2) {
+        if self.target != Some(target) {
+            self.target = Some(target);
+            self.integral = vec2(0.0, 0.0);
+        }
+    }
+}
+
+impl Pilot for HoverPid {
+    fn fly(&mut self, view: &View) -> Input {
+        let target = *self.target.get_or_insert(view.pos);
+        let error = target - view.pos;
+
+        self.integral += error * view.delta_t;
+        // don't wind up forever while stuck against a wall
+        self.integral = clamp_length(self.integral, 50.0);
+
+        let accel = error * self.kp + self.integral * self.ki - view.vel * self.kd;
+        let accel = clamp_length(accel, view.boost_power * 0.5);
+        self.steer.toward(view, accel)
+    }
+}
+
+/// Hovers at each point in turn, moving on once it has settled near the current one.
+#[derive(Debug, Clone)]
+pub struct WaypointFollower {
+    pub course: Course,
+    /// meters
+    pub radius: f32,
+    next: usize,
+    hover: HoverPid,
+}
+
+impl WaypointFollower {
+    pub fn new(course: Course) -> Self {
+        Self {
+            course,
+            radius: 15.0,
+            next: 0,
+            hover: HoverPid::default(),
+        }
+    }
+}
+
+impl Pilot for WaypointFollower {
+    fn fly(&mut self, view: &View) -> Input {
+        if let Some(point) = self.course.points.get(self.next) {
+            let settled = view.vel.length() < 10.0;
+            if (*point - view.pos).length() < self.radius && settled {
+                self.next += 1;
+                if self.course.looped {
+                    self.next %= self.course.points.len();
+                }
+            }
+        }
+
+        if let Some(point) = self.course.points.get(self.next) {
+            self.hover.retarget(*point);
+        }
+        self.hover.fly(view)
+    }
+}
+
+/// Flies through the course without stopping.
+#[derive(Debug, Clone)]
+pub struct RaceLine {
+    pub course: Course,
+    /// meters per second
+    pub cruise_speed: f32,
+    /// meters, how close counts as passing a point
+    pub radius: f32,
+    next: usize,
+    steer: Steer,
+    hover: HoverPid,
+}
+
+impl RaceLine {
+    pub fn new(course: Course) -> Self {
+        Self {
+            course,
+            cruise_speed: 40.0,
+            radius: 30.0,
+            next: 0,
+            steer: Steer::default(),
+            hover: HoverPid::default(),
+        }
+    }
+}
+
+impl Pilot for RaceLine {
+    fn fly(&mut self, view: &View) -> Input {
+        let point = match self.course.points.get(self.next) {
+            Some(point) => *point,
+            None => {
+                // finished a course that doesn't loop, wait at the end
+                if let Some(last) = self.course.points.last() {
+                    self.hover.retarget(*last);
+                }
+                return self.hover.fly(view);
+            }
+        };
+
+        if (point - view.pos).length() < self.radius {
+            self.next += 1;
+            if self.course.looped {
+                self.next %= self.course.points.len();
+            }
+        }
+
+        let to_point = point - view.pos;
+        let distance = to_point.length();
+        let desired_vel = if distance > 0.001 {
+            to_point / distance * self.cruise_speed.min(distance * 2.0 + 10.0)
+        } else {
+            vec2(0.0, 0.0)
+        };
+        let accel = clamp_length((desired_vel - view.vel) * 1.5, view.boost_power * 0.7);
+        self.steer.toward(view, accel)
+    }
+}
+
+fn wrap_angle(radians: f32) -> f32 {
+    (radians + PI).rem_euclid(TAU) - PI
+}
+
+fn clamp_length(v: Vec2, max: f32) -> Vec2 {
+    let len = v.length();
+    if len > max {
+        v * (max / len)
+    } else {
+        v
+    }
+}
+
+#[test]
+fn hover_boosts_only_when_low() {
+    let mut view = View {
+        pos: vec2(0.0, 0.0),
+        vel: vec2(0.0, 0.0),
+        rot: 0.0,
+        rot_vel: 0.0,
+        gravity: -9.8,
+        boost_power: 30.0,
+        delta_t: 1.0 / 60.0,
+    };
+
+    let mut below = HoverPid {
+        target: Some(vec2(0.0, 50.0)),
+        ..Default::default()
+    };
+    let boosts = (0..60).filter(|_| below.fly(&view).up).count();
+    assert!(boosts > 30);
+
+    view.vel = vec2(0.0, 30.0);
+    let mut above = HoverPid {
+        target: Some(vec2(0.0, -50.0)),
+        ..Default::default()
+    };
+    assert!((0..60).all(|_| !above.fly(&view).up));
+}
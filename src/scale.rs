@@ -0,0 +1,50 @@
+use crate::constants::Settings;
+use crate::system::{Collides, Hull, Input, Wireframe};
+use crate::util::scaled_polyline;
+use macroquad::prelude::*;
+
+/// Stretches an entity's `Wireframe` when drawing and its `Collides` when colliding. Scaled
+/// entities get their collision shape rebuilt from their `Hull`, or wireframe without one,
+/// whenever the scale changes.
+#[derive(Debug, Clone)]
+pub struct Scale {
+    /// multiplier along local x and y
+    pub size: Vec2,
+    /// the size the collision shape was last built for
+    applied: Option<Vec2>,
+}
+
+/// meters, defaults to 1.0 for entities without one
+#[derive(Debug, Clone)]
+pub struct LineWidth(pub f32);
+
+impl Scale {
+    pub fn new(size: Vec2) -> Self {
+        Self {
+            size,
+            applied: None,
+        }
+    }
+
+    pub fn uniform(size: f32) -> Self {
+        Self::new(vec2(size, size))
+    }
+
+    pub fn system(w: &mut hecs::World) {
+        // ships are sized from settings
+        let ship_size = Settings::get(w).ship_size;
+        for (_id, (_input, scale)) in w.query_mut::<(&Input, &mut Scale)>() {
+            scale.size = vec2(ship_size, ship_size);
+        }
+
+        for (_id, (scale, wireframe, hull, collides)) in
+            w.query_mut::<(&mut Scale, &Wireframe, Option<&Hull>, &mut Collides)>()
+        {
+            if scale.applied != Some(scale.size) {
+                let outline = hull.map(|h| &h.0).unwrap_or(&wireframe.0);
+                collides.0 = Box::new(scaled_polyline(outline, scale.size));
+                scale.applied = Some(scale.size);
+            }
+        }
+    }
+}
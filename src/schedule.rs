@@ -0,0 +1,267 @@
+use crate::commands::Commands;
+use macroquad::miniquad::date;
+use macroquad::prelude::*;
+use macroquad::ui::{hash, widgets::Window, Ui};
+
+/// Systems run in stage order. Within a stage they run in the order they were added unless an
+/// `after` or `before` constraint says otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    /// turning what pilots want into forces
+    Input,
+    Physics,
+    Gameplay,
+    /// removing what's done
+    Cleanup,
+}
+
+pub type System = fn(&mut hecs::World);
+
+/// The systems run by each `Heli::step`.
+#[derive(Default)]
+pub struct Schedule {
+    systems: Vec<Entry>,
+    /// indices into systems, recomputed when systems are added
+    order: Option<Vec<usize>>,
+}
+
+struct Entry {
+    name: &'static str,
+    stage: Stage,
+    run: System,
+    after: Vec<&'static str>,
+    before: Vec<&'static str>,
+    enabled: bool,
+    /// seconds, smoothed over recent runs
+    time: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScheduleError {
+    /// a constraint names a system that doesn't exist
+    Unknown(&'static str),
+    /// constraints between these systems contradict each other
+    Cycle(Vec<&'static str>),
+    /// a constraint between systems in different stages
+    CrossStage(&'static str, &'static str),
+}
+
+/// While this exists the profiler panel is shown. Toggled with F3.
+#[derive(Debug, Clone, Copy)]
+pub struct Profiler;
+
+/// weight of the latest run in the smoothed timings
+const SMOOTHING: f64 = 0.1;
+
+impl Schedule {
+    /// Adds a system, chain `after` and `before` to constrain it.
+    pub fn add(&mut self, name: &'static str, stage: Stage, run: System) -> &mut Self {
+        assert!(
+            self.systems.iter().all(|s| s.name != name),
+            "system {} added twice",
+            name
+        );
+        self.systems.push(Entry {
+            name,
+            stage,
+            run,
+            after: Vec::new(),
+            before: Vec::new(),
+            enabled: true,
+            time: 0.0,
+        });
+        self.order = None;
+        self
+    }
+
+    /// The system added last runs after `name`.
+    pub fn after(&mut self, name: &'static str) -> &mut Self {
+        self.systems.last_mut().unwrap().after.push(name);
+        self.order = None;
+        self
+    }
+
+    /// The system added last runs before `name`.
+    pub fn before(&mut self, name: &'static str) -> &mut Self {
+        self.systems.last_mut().unwrap().before.push(name);
+        self.order = None;
+        self
+    }
+
+    /// False if there is no such system.
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        match self.systems.iter_mut().find(|s| s.name == name) {
+            Some(system) => {
+                system.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Names in the order they run.
+    pub fn order(&self) -> Result<Vec<&'static str>, ScheduleError> {
+        Ok(self
+            .sorted()?
+            .into_iter()
+            .map(|i| self.systems[i].name)
+            .collect())
+    }
+
+    /// Runs every enabled system once, applying queued `Commands` after each stage. Panics if the
+    /// constraints can't be satisfied.
+    pub fn run(&mut self, w: &mut hecs::World) {
+        if self.order.is_none() {
+            match self.sorted() {
+                Ok(order) => self.order = Some(order),
+                Err(e) => panic!("can't order systems: {:?}", e),
+            }
+        }
+        let order = self.order.as_ref().unwrap();
+        for (n, &i) in order.iter().enumerate() {
+            let system = &mut self.systems[i];
+            if system.enabled {
+                let start = date::now();
+                (system.run)(w);
+                let elapsed = date::now() - start;
+                system.time += (elapsed - system.time) * SMOOTHING;
+            } else {
+                system.time = 0.0;
+            }
+
+            let stage = system.stage;
+            let last_in_stage = order
+                .get(n + 1)
+                .map(|next| self.systems[*next].stage != stage)
+                .unwrap_or(true);
+            if last_in_stage {
+                Commands::apply(w);
+            }
+        }
+    }
+
+    /// Timings, with a checkbox per system to turn it off.
+    pub fn ui(&mut self, ui: &mut Ui) {
+        let order = match &self.order {
+            Some(order) => order.clone(),
+            None => return,
+        };
+        let total: f64 = self.systems.iter().map(|s| s.time).sum();
+        Window::new(hash!(), vec2(10.0, 560.0), vec2(400.0, 300.0))
+            .label("profiler")
+            .ui(ui, |ui| {
+                ui.label(None, &format!("total {:.3} ms", total * 1000.0));
+                for i in order {
+                    let system = &mut self.systems[i];
+                    let label = format!(
+                        "{:?} {} {:.3} ms",
+                        system.stage,
+                        system.name,
+                        system.time * 1000.0
+                    );
+                    ui.checkbox(hash!(system.name), &label, &mut system.enabled);
+                }
+            });
+    }
+
+    /// Stable topological sort within each stage.
+    fn sorted(&self) -> Result<Vec<usize>, ScheduleError> {
+        let index = |name: &'static str| {
+            self.systems
+                .iter()
+                .position(|s| s.name == name)
+                .ok_or(ScheduleError::Unknown(name))
+        };
+
+        // edges[i] lists the systems that must run before i
+        let mut edges: Vec<Vec<usize>> = vec![Vec::new(); self.systems.len()];
+        for (i, system) in self.systems.iter().enumerate() {
+            for after in &system.after {
+                edges[i].push(index(after)?);
+            }
+            for before in &system.before {
+                edges[index(before)?].push(i);
+            }
+        }
+        for (i, before) in edges.iter().enumerate() {
+            for &j in before {
+                if self.systems[i].stage != self.systems[j].stage {
+                    return Err(ScheduleError::CrossStage(
+                        self.systems[j].name,
+                        self.systems[i].name,
+                    ));
+                }
+            }
+        }
+
+        let mut ret: Vec<usize> = Vec::new();
+        let mut stages: Vec<Stage> = self.systems.iter().map(|s| s.stage).collect();
+        stages.sort();
+        stages.dedup();
+        for stage in stages {
+            let mut pending: Vec<usize> = (0..self.systems.len())
+                .filter(|i| self.systems[*i].stage == stage)
+                .collect();
+            while !pending.is_empty() {
+                let ready = pending
+                    .iter()
+                    .position(|i| edges[*i].iter().all(|j| ret.contains(j)));
+                match ready {
+                    Some(p) => ret.push(pending.remove(p)),
+                    None => {
+                        let names = pending.iter().map(|i| self.systems[*i].name).collect();
+                        return Err(ScheduleError::Cycle(names));
+                    }
+                }
+            }
+        }
+        Ok(ret)
+    }
+}
+
+impl Profiler {
+    pub fn enabled(w: &hecs::World) -> bool {
+        w.query::<(&Profiler,)>().iter().next().is_some()
+    }
+
+    pub fn toggle(w: &mut hecs::World) {
+        let existing: Vec<hecs::Entity> = w
+            .query::<(&Profiler,)>()
+            .iter()
+            .map(|(ent, _)| ent)
+            .collect();
+        if existing.is_empty() {
+            w.spawn((Profiler,));
+        }
+        for ent in existing {
+            w.despawn(ent).unwrap();
+        }
+    }
+}
+
+#[test]
+fn stages_then_constraints() {
+    fn nothing(_: &mut hecs::World) {}
+
+    let mut schedule = Schedule::default();
+    schedule
+        .add("cleanup", Stage::Cleanup, nothing)
+        .add("b", Stage::Physics, nothing)
+        .add("a", Stage::Physics, nothing)
+        .before("b")
+        .add("c", Stage::Physics, nothing)
+        .add("input", Stage::Input, nothing);
+    assert_eq!(
+        schedule.order(),
+        Ok(vec!["input", "a", "b", "c", "cleanup"])
+    );
+
+    schedule
+        .add("d", Stage::Physics, nothing)
+        .before("a")
+        .after("b");
+    assert_eq!(
+        schedule.order(),
+        Err(ScheduleError::Cycle(vec!["b", "a", "d"]))
+    );
+}
@@ -0,0 +1,531 @@
+use crate::pickup::{Fuel, PowerUps};
+use crate::script::Messages;
+use crate::snapshot::{DataError, DataFile};
+use crate::system::{ships, Contacts, Pos, Rot, RotVel, Time, Vel};
+use crate::weapon::{Health, Hits};
+use macroquad::prelude::*;
+
+pub const RULES_HEADER: &str = "heli rules 1";
+
+/// How a two player match is won. Every field can be set by name so variants can be tried
+/// without rebuilding, see `Rules::load`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rules {
+    /// the match ends when someone has this many, 0 for no limit
+    pub points_to_win: u32,
+    /// seconds, the match ends when it runs out, 0 for no limit
+    pub time_limit: f32,
+    /// meters per second, slower hits don't count as rams
+    pub ram_speed: f32,
+    /// for the faster of two ships that hit each other
+    pub ram_points: u32,
+    /// for the last ship to touch one that hits a hazard
+    pub hazard_points: u32,
+    /// for the opponent when a ship hits a hazard nobody pushed it into
+    pub crash_points: u32,
+    /// for the shot that takes a ship's last health
+    pub kill_points: u32,
+    /// seconds a touch still counts as a push
+    pub push_time: f32,
+    /// put the ships back at their spawn points after each score
+    pub reset_on_score: bool,
+    /// seconds between a score and the reset
+    pub reset_delay: f32,
+}
+
+/// field names, in the order snapshots write them
+const RULE_NAMES: [&str; 10] = [
+    "points_to_win",
+    "time_limit",
+    "ram_speed",
+    "ram_points",
+    "hazard_points",
+    "crash_points",
+    "kill_points",
+    "push_time",
+    "reset_on_score",
+    "reset_delay",
+];
+
+/// Where ship `.0` goes at the start of each round.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpawnPoint(pub usize);
+
+/// Scores for whoever pushed a ship into it.
+#[derive(Debug, Clone, Copy)]
+pub struct Hazard;
+
+/// The match being played, ships are numbered in the order `ships` returns them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+    pub rules: Rules,
+    pub scores: Vec<u32>,
+    /// world time the match began
+    pub started: f64,
+    pub state: MatchState,
+    /// for each ship, which ship touched it last and when
+    pub touched: Vec<Option<(usize, f64)>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchState {
+    Playing,
+    /// ships go back to their spawn points at this world time
+    Resetting(f64),
+    /// results are up until a rematch
+    Over,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            points_to_win: 5,
+            time_limit: 180.0,
+            ram_speed: 40.0,
+            ram_points: 1,
+            hazard_points: 2,
+            crash_points: 0,
+            kill_points: 3,
+            push_time: 3.0,
+            reset_on_score: true,
+            reset_delay: 2.0,
+        }
+    }
+}
+
+impl Rules {
+    pub fn get(&self, name: &str) -> Option<f32> {
+        Some(match name {
+            "points_to_win" => self.points_to_win as f32,
+            "time_limit" => self.time_limit,
+            "ram_speed" => self.ram_speed,
+            "ram_points" => self.ram_points as f32,
+            "hazard_points" => self.hazard_points as f32,
+            "crash_points" => self.crash_points as f32,
+            "kill_points" => self.kill_points as f32,
+            "push_time" => self.push_time,
+            "reset_on_score" => self.reset_on_score as u8 as f32,
+            "reset_delay" => self.reset_delay,
+            _ => return None,
+        })
+    }
+
+    /// Sets a field by name, false if there is no such field. Flags are set by anything but 0.
+    pub fn set(&mut self, name: &str, value: f32) -> bool {
+        let points = value.max(0.0) as u32;
+        match name {
+            "points_to_win" => self.points_to_win = points,
+            "time_limit" => self.time_limit = value,
+            "ram_speed" => self.ram_speed = value,
+            "ram_points" => self.ram_points = points,
+            "hazard_points" => self.hazard_points = points,
+            "crash_points" => self.crash_points = points,
+            "kill_points" => self.kill_points = points,
+            "push_time" => self.push_time = value,
+            "reset_on_score" => self.reset_on_score = value != 0.0,
+            "reset_delay" => self.reset_delay = value,
+            _ => return false,
+        }
+        true
+    }
+
+    /// The default rules changed by `text`: a `RULES_HEADER` line, then lines of
+    /// `<field> <value>` with fields named like `Rules`' own.
+    pub fn load(text: &str) -> Result<Self, DataError> {
+        let mut lines = text.lines().enumerate();
+        match lines.next() {
+            Some((_, RULES_HEADER)) => {}
+            _ => return Err(DataError::MissingHeader("rules")),
+        }
+
+        let mut ret = Self::default();
+        for (i, line) in lines {
+            let line_number = i + 1;
+            match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+                [] => {}
+                [name, value] => {
+                    let bad = || DataError::BadValue("rules", line_number, name.to_string());
+                    let value = value.parse().map_err(|_| bad())?;
+                    if !ret.set(name, value) {
+                        return Err(DataError::UnknownField(
+                            "rules",
+                            line_number,
+                            name.to_string(),
+                        ));
+                    }
+                }
+                [name, ..] => {
+                    return Err(DataError::BadValue("rules", line_number, name.to_string()))
+                }
+            }
+        }
+        Ok(ret)
+    }
+}
+
+impl DataFile for Rules {
+    fn load(text: &str) -> Result<Self, DataError> {
+        Rules::load(text)
+    }
+}
+
+impl Match {
+    /// Starts with a reset so ships begin at their spawn points.
+    pub fn new(rules: Rules, players: usize, now: f64) -> Self {
+        Self {
+            rules,
+            scores: vec![0; players],
+            started: now,
+            state: MatchState::Resetting(now),
+            touched: vec![None; players],
+        }
+    }
+
+    /// Seconds left, None without a time limit.
+    pub fn time_left(&self, now: f64) -> Option<f64> {
+        if self.rules.time_limit > 0.0 {
+            Some((self.started + self.rules.time_limit as f64 - now).max(0.0))
+        } else {
+            None
+        }
+    }
+
+    /// Index of the player with the most points, None for a draw.
+    pub fn leader(&self) -> Option<usize> {
+        let best = *self.scores.iter().max()?;
+        let mut leaders = (0..self.scores.len()).filter(|i| self.scores[*i] == best);
+        match (leaders.next(), leaders.next()) {
+            (Some(leader), None) => Some(leader),
+            _ => None,
+        }
+    }
+
+    /// Written as `Match::parse` reads it, without spaces so it fits in a snapshot.
+    pub fn to_text(&self) -> String {
+        let list = |items: Vec<String>| items.join(",");
+        let state = match self.state {
+            MatchState::Playing => "playing".to_string(),
+            MatchState::Resetting(at) => format!("reset@{}", at),
+            MatchState::Over => "over".to_string(),
+        };
+        let touched = self
+            .touched
+            .iter()
+            .map(|t| match t {
+                Some((by, at)) => format!("{}@{}", by, at),
+                None => "-".to_string(),
+            })
+            .collect();
+        let rules = RULE_NAMES
+            .iter()
+            .map(|name| self.rules.get(name).unwrap().to_string())
+            .collect();
+        format!(
+            "{}/{}/{}/{}/{}",
+            list(self.scores.iter().map(|s| s.to_string()).collect()),
+            self.started,
+            state,
+            list(touched),
+            list(rules)
+        )
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        let parts: Vec<&str> = s.split('/').collect();
+        if parts.len() != 5 {
+            return None;
+        }
+        let at = |s: &str| -> Option<(usize, f64)> {
+            let mut split = s.splitn(2, '@');
+            Some((split.next()?.parse().ok()?, split.next()?.parse().ok()?))
+        };
+        let scores = parts[0]
+            .split(',')
+            .map(|s| s.parse().ok())
+            .collect::<Option<Vec<u32>>>()?;
+        let state = match parts[2] {
+            "playing" => MatchState::Playing,
+            "over" => MatchState::Over,
+            reset => MatchState::Resetting(reset.strip_prefix("reset@")?.parse().ok()?),
+        };
+        let touched = parts[3]
+            .split(',')
+            .map(|t| {
+                if t == "-" {
+                    Some(None)
+                } else {
+                    at(t).map(Some)
+                }
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let values: Vec<&str> = parts[4].split(',').collect();
+        if values.len() != RULE_NAMES.len() || touched.len() != scores.len() {
+            return None;
+        }
+        let mut rules = Rules::default();
+        for (name, value) in RULE_NAMES.iter().zip(values) {
+            rules.set(name, value.parse().ok()?);
+        }
+        Some(Self {
+            rules,
+            scores,
+            started: parts[1].parse().ok()?,
+            state,
+            touched,
+        })
+    }
+
+    /// Scores rams and hazard hits from this tick's `Contacts`, resets rounds and ends the
+    /// match.
+    pub fn system(w: &mut hecs::World) {
+        let mut m = match w.query::<(&Match,)>().iter().next() {
+            Some((_, (m,))) => m.clone(),
+            None => return,
+        };
+        let now = Time::get(w).now;
+        let ships = ships(w);
+        let mut messages = Vec::new();
+
+        if let MatchState::Resetting(at) = m.state {
+            if now >= at {
+                reset_ships(w, &ships);
+                m.touched = vec![None; m.scores.len()];
+                m.state = MatchState::Playing;
+            }
+        }
+
+        let contacts = w
+            .query::<(&Contacts,)>()
+            .iter()
+            .next()
+            .map(|(_, (c,))| c.0.clone())
+            .unwrap_or_default();
+        for contact in contacts {
+            if m.state != MatchState::Playing {
+                break;
+            }
+            let a = match ships.iter().position(|s| *s == contact.a) {
+                Some(a) if a < m.scores.len() => a,
+                _ => continue,
+            };
+            let b = ships.iter().position(|s| *s == contact.b);
+            if let Some(b) = b.filter(|b| *b < m.scores.len()) {
+                m.touched[a] = Some((b, now));
+                // both ships get a contact, the one that was moving toward the other scores
+                let rammed =
+                    contact.speed >= m.rules.ram_speed && contact.own_speed * 2.0 > contact.speed;
+                if rammed && m.rules.ram_points > 0 {
+                    m.score(b, a, m.rules.ram_points, now);
+                    messages.push(format!("player {} rams player {}", a + 1, b + 1));
+                }
+            } else if w.get::<Hazard>(contact.b).is_ok() {
+                match m.touched[a] {
+                    Some((by, at)) if now - at <= m.rules.push_time as f64 => {
+                        m.score(a, by, m.rules.hazard_points, now);
+                        messages.push(format!(
+                            "player {} pushes player {} into a hazard",
+                            by + 1,
+                            a + 1
+                        ));
+                    }
+                    _ if m.rules.crash_points > 0 => {
+                        for other in (0..m.scores.len()).filter(|o| *o != a) {
+                            m.score(a, other, m.rules.crash_points, now);
+                        }
+                        messages.push(format!("player {} crashes", a + 1));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let hits = w
+            .query::<(&Hits,)>()
+            .iter()
+            .next()
+            .map(|(_, (h,))| h.0.clone())
+            .unwrap_or_default();
+        for hit in hits.into_iter().filter(|h| h.fatal) {
+            if m.state != MatchState::Playing || m.rules.kill_points == 0 {
+                break;
+            }
+            let victim = ships.iter().position(|s| *s == hit.target);
+            let shooter = ships.iter().position(|s| *s == hit.owner);
+            if let (Some(victim), Some(shooter)) = (victim, shooter) {
+                if victim.max(shooter) >= m.scores.len() || victim == shooter {
+                    continue;
+                }
+                m.score(victim, shooter, m.rules.kill_points, now);
+                messages.push(format!(
+                    "player {} shoots down player {}",
+                    shooter + 1,
+                    victim + 1
+                ));
+                if !m.rules.reset_on_score {
+                    heal(w, hit.target);
+                }
+            }
+        }
+
+        let out_of_time = m.time_left(now) == Some(0.0);
+        let won = m.rules.points_to_win > 0 && m.scores.iter().any(|s| *s >= m.rules.points_to_win);
+        if m.state != MatchState::Over && (out_of_time || won) {
+            m.state = MatchState::Over;
+        }
+
+        for text in messages {
+            Messages::show(w, text, now + m.rules.reset_delay.max(2.0) as f64);
+        }
+        for (_, (stored,)) in w.query_mut::<(&mut Match,)>() {
+            *stored = m.clone();
+        }
+    }
+
+    /// `points` for `scorer` at the expense of `victim`.
+    fn score(&mut self, victim: usize, scorer: usize, points: u32, now: f64) {
+        self.scores[scorer] += points;
+        self.touched[victim] = None;
+        if self.rules.reset_on_score {
+            self.state = MatchState::Resetting(now + self.rules.reset_delay as f64);
+        }
+    }
+
+    /// Starts over with the same rules once the results are up. Returns whether it did.
+    pub fn rematch(w: &mut hecs::World) -> bool {
+        let now = Time::get(w).now;
+        let mut restarted = false;
+        for (_, (m,)) in w.query_mut::<(&mut Match,)>() {
+            if m.state == MatchState::Over {
+                *m = Match::new(m.rules.clone(), m.scores.len(), now);
+                restarted = true;
+            }
+        }
+        restarted
+    }
+
+    /// Scores and time along the top, results when the match is over. Expects the default
+    /// camera to be set.
+    pub fn draw_hud(w: &hecs::World) {
+        let now = Time::get(w).now;
+        for (_, (m,)) in w.query::<(&Match,)>().iter() {
+            let scores: Vec<String> = m.scores.iter().map(|s| s.to_string()).collect();
+            let mut top = scores.join(" : ");
+            if let Some(left) = m.time_left(now) {
+                let seconds = left.ceil() as u32;
+                top.push_str(&format!("    {}:{:02}", seconds / 60, seconds % 60));
+            }
+            let width = measure_text(&top, None, 40, 1.0).width;
+            draw_text(&top, (screen_width() - width) / 2.0, 40.0, 40.0, WHITE);
+
+            if m.state != MatchState::Over {
+                continue;
+            }
+            let (w_box, h_box) = (420.0, 100.0 + 40.0 * m.scores.len() as f32);
+            let (x, y) = (
+                (screen_width() - w_box) / 2.0,
+                (screen_height() - h_box) / 2.0,
+            );
+            draw_rectangle(x, y, w_box, h_box, Color::new(0.0, 0.0, 0.0, 0.8));
+            let title = match m.leader() {
+                Some(leader) => format!("player {} wins", leader + 1),
+                None => "draw".to_string(),
+            };
+            draw_text(&title, x + 20.0, y + 50.0, 50.0, GREEN);
+            for (i, score) in m.scores.iter().enumerate() {
+                let line = format!("player {}: {}", i + 1, score);
+                draw_text(&line, x + 20.0, y + 90.0 + 40.0 * i as f32, 30.0, WHITE);
+            }
+            draw_text(
+                "enter for a rematch",
+                x + 20.0,
+                y + h_box - 15.0,
+                24.0,
+                LIGHTGRAY,
+            );
+        }
+    }
+}
+
+fn heal(w: &mut hecs::World, ent: hecs::Entity) {
+    if let Ok(mut health) = w.get_mut::<Health>(ent) {
+        health.hp = health.max;
+    }
+}
+
+/// Puts each ship at its spawn point, at rest, upright, healed and refueled without power-ups.
+fn reset_ships(w: &mut hecs::World, ships: &[hecs::Entity]) {
+    for ship in ships {
+        heal(w, *ship);
+        if let Ok(mut fuel) = w.get_mut::<Fuel>(*ship) {
+            fuel.amount = fuel.max;
+        }
+        let _ = w.remove_one::<PowerUps>(*ship);
+    }
+    let points: Vec<(usize, Vec2)> = w
+        .query::<(&SpawnPoint, &Pos)>()
+        .iter()
+        .map(|(_, (sp, pos))| (sp.0, pos.0))
+        .collect();
+    for (index, pos) in points {
+        let ship = match ships.get(index) {
+            Some(ship) => *ship,
+            None => continue,
+        };
+        if let Ok((p, v, r, rv)) =
+            w.query_one_mut::<(&mut Pos, &mut Vel, &mut Rot, Option<&mut RotVel>)>(ship)
+        {
+            p.0 = pos;
+            v.0 = vec2(0.0, 0.0);
+            r.0 = 0.0;
+            if let Some(rv) = rv {
+                rv.0 = 0.0;
+            }
+        }
+    }
+}
+
+#[test]
+fn rams_score_and_reset() {
+    use crate::system::{spawn_ship, Contact};
+
+    let mut w = hecs::World::new();
+    w.spawn((Time::default(),));
+    let a = spawn_ship(&mut w, vec2(0.0, 0.0), WHITE);
+    let b = spawn_ship(&mut w, vec2(10.0, 0.0), WHITE);
+    w.spawn((Pos(vec2(-100.0, 0.0)), SpawnPoint(0)));
+    w.spawn((Pos(vec2(100.0, 0.0)), SpawnPoint(1)));
+    let rules = Rules::load(&format!(
+        "{}\npoints_to_win 1\nreset_delay 0\n",
+        RULES_HEADER
+    ))
+    .unwrap();
+    w.spawn((Match::new(rules, 2, 0.0),));
+
+    Match::system(&mut w);
+    assert_eq!(w.get::<Pos>(a).unwrap().0, vec2(-100.0, 0.0));
+
+    // b drifts into a's path, a hits it hard
+    let hit = |from, to, own_speed| Contact {
+        a: from,
+        b: to,
+        point: vec2(0.0, 0.0),
+        normal: vec2(1.0, 0.0),
+        speed: 50.0,
+        own_speed,
+    };
+    w.spawn((Contacts(vec![hit(b, a, 5.0), hit(a, b, 45.0)]),));
+    Match::system(&mut w);
+    let m = w.query::<(&Match,)>().iter().next().unwrap().1 .0.clone();
+    assert_eq!(m.scores, vec![1, 0]);
+    assert_eq!(m.state, MatchState::Over);
+    assert_eq!(Match::parse(&m.to_text()), Some(m));
+
+    assert_eq!(
+        Rules::load(&format!("{}\npoints 3\n", RULES_HEADER)),
+        Err(DataError::UnknownField("rules", 2, "points".into()))
+    );
+    assert_eq!(
+        DataError::MissingHeader("rules").to_string(),
+        "not a heli rules file"
+    );
+}
@@ -0,0 +1,483 @@
+//! Level logic written in [Rhai](https://rhai.rs).
+//!
+//! Scripts never touch components. They call the functions registered in `Script::load`, which
+//! read a copy of the world taken before each callback and queue commands that are applied once
+//! the callback returns. A script may define any of these callbacks:
+//!
+//! - `on_start()` once, before the first tick
+//! - `on_tick(dt)` every tick
+//! - `on_enter(trigger, ship)` when ship number `ship` flies into the named trigger area
+//!
+//! The first error stops the script and stays on screen with its file and line.
+
+use crate::constants::{Settings, WORLD_HEIGHT};
+use crate::snapshot::shape_by_name;
+use crate::spawner::Registry;
+use crate::system::{Collides, Drag, Grav, Input, Pos, Rot, RotVel, Time, Vel, Wireframe};
+use crate::util::wireframe_to_polyline;
+use alloc::sync::Arc;
+use hecs::Entity;
+use macroquad::prelude::*;
+use rhai::{
+    CallFnOptions, Dynamic, Engine, EvalAltResult, ImmutableString, Position, Scope, AST, INT,
+};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// A running level script.
+pub struct Script {
+    /// shown in error messages
+    pub file: String,
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    shared: Arc<Mutex<Shared>>,
+    /// entities spawned by the script, by the handle the script was given
+    handles: HashMap<INT, Entity>,
+    /// ships inside each trigger as of last tick
+    inside: Vec<(Entity, Entity)>,
+    started: bool,
+    /// set by the first failure, the script doesn't run after that
+    pub error: Option<String>,
+}
+
+/// A named area scripts hear about when ships enter it. Centered on the entity's `Pos`.
+#[derive(Debug, Clone)]
+pub struct TriggerArea {
+    pub name: String,
+    /// meters, width and height
+    pub size: Vec2,
+}
+
+/// Text shown on screen until a world time.
+#[derive(Debug, Default)]
+pub struct Messages(pub Vec<(String, f64)>);
+
+impl Messages {
+    /// Shows `text` until world time `until`.
+    pub fn show(w: &mut hecs::World, text: String, until: f64) {
+        match w.query_mut::<(&mut Messages,)>().into_iter().next() {
+            Some((_, (messages,))) => messages.0.push((text, until)),
+            None => {
+                w.spawn((Messages(vec![(text, until)]),));
+            }
+        }
+    }
+
+    /// Drops messages whose time is up.
+    pub fn system(w: &mut hecs::World) {
+        let now = Time::get(w).now;
+        for (_, (messages,)) in w.query_mut::<(&mut Messages,)>() {
+            messages.0.retain(|(_, until)| *until > now);
+        }
+    }
+}
+
+/// How the level ended, if it has.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Won(String),
+    Lost(String),
+}
+
+/// What scripts can see and what they've asked for.
+#[derive(Default)]
+struct Shared {
+    time: f64,
+    /// positions of ships, ordered by entity id
+    ships: Vec<Vec2>,
+    /// positions of entities the script spawned, by handle
+    spawned: HashMap<INT, Vec2>,
+    next_handle: INT,
+    commands: Vec<Command>,
+}
+
+enum Command {
+    Spawn {
+        handle: INT,
+        shape: &'static [(f32, f32)],
+        pos: Vec2,
+    },
+    Despawn(INT),
+    Effect {
+        id: String,
+        pos: Vec2,
+    },
+    Set(String, f32),
+    Message(String, f64),
+    Trigger {
+        name: String,
+        pos: Vec2,
+        size: Vec2,
+    },
+    End(Outcome),
+}
+
+impl Script {
+    /// Compiles and runs the top level of the script. Errors are kept in `error` rather than
+    /// returned so they show up in game like any other script error.
+    pub fn load(file: &str, source: &str) -> Self {
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let mut ret = Self {
+            file: file.to_string(),
+            engine: engine(&shared),
+            ast: AST::default(),
+            scope: Scope::new(),
+            shared,
+            handles: HashMap::new(),
+            inside: Vec::new(),
+            started: false,
+            error: None,
+        };
+        match ret.engine.compile(source) {
+            Ok(ast) => {
+                ret.ast = ast;
+                if let Err(e) = ret.engine.run_ast_with_scope(&mut ret.scope, &ret.ast) {
+                    ret.error = Some(ret.describe(e.position(), &e.to_string()));
+                }
+            }
+            Err(e) => ret.error = Some(ret.describe(e.1, &e.to_string())),
+        }
+        ret
+    }
+
+    pub fn system(w: &mut hecs::World) {
+        let ent = match w.query::<(&Script,)>().iter().next() {
+            Some((ent, _)) => ent,
+            None => return,
+        };
+        // taken out so callbacks can run while the world is borrowed
+        let mut script = w.remove_one::<Script>(ent).unwrap();
+        script.tick(w);
+        w.insert_one(ent, script).unwrap();
+    }
+
+    fn tick(&mut self, w: &mut hecs::World) {
+        if self.error.is_some() {
+            return;
+        }
+        let Time { now, delta } = Time::get(w);
+        let ships: Vec<Entity> = {
+            let mut ships: Vec<Entity> = w
+                .query::<(&Input, &Pos)>()
+                .iter()
+                .map(|(ent, _)| ent)
+                .collect();
+            ships.sort_by_key(|ent| ent.id());
+            ships
+        };
+
+        {
+            let mut shared = self.shared.lock().unwrap();
+            shared.time = now;
+            shared.ships = ships.iter().map(|s| w.get::<Pos>(*s).unwrap().0).collect();
+            shared.spawned = self
+                .handles
+                .iter()
+                .filter_map(|(handle, ent)| Some((*handle, w.get::<Pos>(*ent).ok()?.0)))
+                .collect();
+        }
+
+        let mut result = Ok(());
+        if !self.started {
+            self.started = true;
+            result = self.call("on_start", ());
+        }
+        if result.is_ok() {
+            result = self.call("on_tick", (delta as f64,));
+        }
+
+        // ships that just flew into a trigger
+        let mut inside: Vec<(Entity, Entity)> = Vec::new();
+        let mut entered: Vec<(String, INT)> = Vec::new();
+        for (trigger_ent, (trigger, pos)) in w.query::<(&TriggerArea, &Pos)>().iter() {
+            for (i, ship) in ships.iter().enumerate() {
+                let offset = (w.get::<Pos>(*ship).unwrap().0 - pos.0).abs();
+                if offset.x <= trigger.size.x / 2.0 && offset.y <= trigger.size.y / 2.0 {
+                    inside.push((trigger_ent, *ship));
+                    if !self.inside.contains(&(trigger_ent, *ship)) {
+                        entered.push((trigger.name.clone(), i as INT));
+                    }
+                }
+            }
+        }
+        self.inside = inside;
+        for (name, ship) in entered {
+            if result.is_ok() {
+                result = self.call("on_enter", (name, ship));
+            }
+        }
+
+        if let Err(e) = result {
+            self.error = Some(e);
+        }
+        self.apply(w, now);
+    }
+
+    /// Calls a callback if the script defines it.
+    fn call(&mut self, name: &str, args: impl rhai::FuncArgs) -> Result<(), String> {
+        // the top level already ran in `load`
+        let options = CallFnOptions::new().eval_ast(false);
+        match self.engine.call_fn_with_options::<Dynamic>(
+            options,
+            &mut self.scope,
+            &self.ast,
+            name,
+            args,
+        ) {
+            Ok(_) => Ok(()),
+            Err(e) => match *e {
+                // only the callback itself, calls it makes to missing functions are still errors
+                EvalAltResult::ErrorFunctionNotFound(ref f, _) if f == name => Ok(()),
+                e => Err(self.describe(e.position(), &e.to_string())),
+            },
+        }
+    }
+
+    fn describe(&self, position: Position, error: &str) -> String {
+        match position.line() {
+            Some(line) => format!("{}:{}: {}", self.file, line, error),
+            None => format!("{}: {}", self.file, error),
+        }
+    }
+
+    fn apply(&mut self, w: &mut hecs::World, now: f64) {
+        let commands = core::mem::take(&mut self.shared.lock().unwrap().commands);
+        for command in commands {
+            match command {
+                Command::Spawn { handle, shape, pos } => {
+                    let ent = w.spawn((
+                        Pos(pos),
+                        Vel(vec2(0.0, 0.0)),
+                        Rot(0.0),
+                        RotVel(0.0),
+                        Grav,
+                        Drag,
+                        WHITE,
+                        Collides(Box::new(wireframe_to_polyline(shape))),
+                        Wireframe(shape.into()),
+                    ));
+                    self.handles.insert(handle, ent);
+                }
+                Command::Despawn(handle) => {
+                    if let Some(ent) = self.handles.remove(&handle) {
+                        let _ = w.despawn(ent);
+                    }
+                }
+                Command::Effect { id, pos } => {
+                    if let Some(def) = Registry::spawner(w, &id) {
+                        let source = w.spawn((Pos(pos),));
+                        def.spawn(w, source);
+                        w.despawn(source).unwrap();
+                    }
+                }
+                Command::Set(name, value) => {
+                    for (_, (settings,)) in w.query_mut::<(&mut Settings,)>() {
+                        settings.set(&name, value);
+                    }
+                }
+                Command::Message(text, seconds) => Messages::show(w, text, now + seconds),
+                Command::Trigger { name, pos, size } => {
+                    w.spawn((Pos(pos), TriggerArea { name, size }));
+                }
+                Command::End(outcome) => {
+                    // the first ending sticks
+                    if w.query::<(&Outcome,)>().iter().next().is_none() {
+                        w.spawn((outcome,));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Outlines trigger areas. Expects the world camera to be set.
+    pub fn draw_world(w: &hecs::World) {
+        let meters_per_screen = 1.0 / WORLD_HEIGHT;
+        let color = Color::new(1.0, 1.0, 1.0, 0.3);
+        for (_, (trigger, pos)) in w.query::<(&TriggerArea, &Pos)>().iter() {
+            let corner = (pos.0 - trigger.size / 2.0) * meters_per_screen;
+            let size = trigger.size * meters_per_screen;
+            draw_rectangle_lines(corner.x, corner.y, size.x, size.y, meters_per_screen, color);
+        }
+    }
+
+    /// Messages, the outcome and script errors. Expects the default camera to be set.
+    pub fn draw_hud(w: &hecs::World) {
+        let mut y = screen_height() * 0.25;
+        let mut centered = |text: &str, size: f32, color: Color| {
+            let width = measure_text(text, None, size as u16, 1.0).width;
+            draw_text(text, (screen_width() - width) / 2.0, y, size, color);
+            y += size;
+        };
+
+        for (_, (outcome,)) in w.query::<(&Outcome,)>().iter() {
+            match outcome {
+                Outcome::Won(text) => centered(text, 60.0, GREEN),
+                Outcome::Lost(text) => centered(text, 60.0, RED),
+            }
+        }
+        for (_, (messages,)) in w.query::<(&Messages,)>().iter() {
+            for (text, _) in &messages.0 {
+                centered(text, 30.0, WHITE);
+            }
+        }
+        for (_, (script,)) in w.query::<(&Script,)>().iter() {
+            if let Some(error) = &script.error {
+                draw_text(error, 10.0, screen_height() - 20.0, 24.0, RED);
+            }
+        }
+    }
+}
+
+/// The functions scripts can call.
+fn engine(shared: &Arc<Mutex<Shared>>) -> Engine {
+    let mut engine = Engine::new();
+    let s = shared.clone();
+    engine.register_fn("time", move || s.lock().unwrap().time);
+
+    let s = shared.clone();
+    engine.register_fn("ship_count", move || s.lock().unwrap().ships.len() as INT);
+    let s = shared.clone();
+    engine.register_fn("ship_x", move |i: INT| ship(&s, i).map(|p| p.x as f64));
+    let s = shared.clone();
+    engine.register_fn("ship_y", move |i: INT| ship(&s, i).map(|p| p.y as f64));
+
+    let s = shared.clone();
+    engine.register_fn(
+        "spawn",
+        move |shape: ImmutableString, x: f64, y: f64| -> Result<INT, Box<EvalAltResult>> {
+            match shape_by_name(&shape) {
+                Some(shape) => {
+                    let mut shared = s.lock().unwrap();
+                    let handle = shared.next_handle;
+                    shared.next_handle += 1;
+                    let pos = vec2(x as f32, y as f32);
+                    shared.spawned.insert(handle, pos);
+                    shared.commands.push(Command::Spawn { handle, shape, pos });
+                    Ok(handle)
+                }
+                None => Err(format!("unknown shape {}", shape).into()),
+            }
+        },
+    );
+    let s = shared.clone();
+    engine.register_fn("despawn", move |handle: INT| {
+        s.lock().unwrap().commands.push(Command::Despawn(handle));
+    });
+    let s = shared.clone();
+    engine.register_fn("x_of", move |handle: INT| {
+        spawned(&s, handle).map(|p| p.x as f64)
+    });
+    let s = shared.clone();
+    engine.register_fn("y_of", move |handle: INT| {
+        spawned(&s, handle).map(|p| p.y as f64)
+    });
+    let s = shared.clone();
+    engine.register_fn("effect", move |id: ImmutableString, x: f64, y: f64| {
+        s.lock().unwrap().commands.push(Command::Effect {
+            id: id.to_string(),
+            pos: vec2(x as f32, y as f32),
+        });
+    });
+
+    let s = shared.clone();
+    engine.register_fn(
+        "set_setting",
+        move |name: ImmutableString, value: f64| -> Result<(), Box<EvalAltResult>> {
+            if !Settings::default().set(&name, value as f32) {
+                return Err(format!("unknown setting {}", name).into());
+            }
+            let command = Command::Set(name.to_string(), value as f32);
+            s.lock().unwrap().commands.push(command);
+            Ok(())
+        },
+    );
+    let s = shared.clone();
+    engine.register_fn("message", move |text: ImmutableString, seconds: f64| {
+        let command = Command::Message(text.to_string(), seconds);
+        s.lock().unwrap().commands.push(command);
+    });
+    let s = shared.clone();
+    engine.register_fn(
+        "trigger",
+        move |name: ImmutableString, x: f64, y: f64, width: f64, height: f64| {
+            s.lock().unwrap().commands.push(Command::Trigger {
+                name: name.to_string(),
+                pos: vec2(x as f32, y as f32),
+                size: vec2(width as f32, height as f32),
+            });
+        },
+    );
+    let s = shared.clone();
+    engine.register_fn("win", move |text: ImmutableString| {
+        let command = Command::End(Outcome::Won(text.to_string()));
+        s.lock().unwrap().commands.push(command);
+    });
+    let s = shared.clone();
+    engine.register_fn("lose", move |text: ImmutableString| {
+        let command = Command::End(Outcome::Lost(text.to_string()));
+        s.lock().unwrap().commands.push(command);
+    });
+    engine
+}
+
+fn ship(shared: &Mutex<Shared>, i: INT) -> Result<Vec2, Box<EvalAltResult>> {
+    let shared = shared.lock().unwrap();
+    match shared.ships.get(i as usize) {
+        Some(pos) if i >= 0 => Ok(*pos),
+        _ => Err(format!("no ship {}", i).into()),
+    }
+}
+
+fn spawned(shared: &Mutex<Shared>, handle: INT) -> Result<Vec2, Box<EvalAltResult>> {
+    match shared.lock().unwrap().spawned.get(&handle) {
+        Some(pos) => Ok(*pos),
+        None => Err(format!("no spawned entity {}", handle).into()),
+    }
+}
+
+#[test]
+fn triggers_and_errors() {
+    let mut w = hecs::World::new();
+    w.spawn((Settings::default(),));
+    w.spawn((Time {
+        now: 0.0,
+        delta: 0.1,
+    },));
+    let ship = crate::system::spawn_ship(&mut w, vec2(0.0, 0.0), WHITE);
+    let source = r#"
+        fn on_start() {
+            trigger("goal", 100.0, 0.0, 20.0, 20.0);
+            set_setting("gravity", 0.0);
+        }
+        fn on_enter(name, ship) {
+            win("ship " + ship + " reached " + name);
+        }
+    "#;
+    w.spawn((Script::load("test.rhai", source),));
+
+    Script::system(&mut w);
+    assert_eq!(Settings::get(&w).gravity, 0.0);
+    assert!(w.query::<(&Outcome,)>().iter().next().is_none());
+
+    w.get_mut::<Pos>(ship).unwrap().0 = vec2(105.0, 5.0);
+    Script::system(&mut w);
+    let outcome = w
+        .query::<(&Outcome,)>()
+        .iter()
+        .next()
+        .map(|(_, (o,))| o.clone());
+    assert_eq!(outcome, Some(Outcome::Won("ship 0 reached goal".into())));
+
+    let broken = Script::load("broken.rhai", "let a = 1;\nlet b = nope;\n");
+    assert!(broken.error.unwrap().starts_with("broken.rhai:2:"));
+
+    // a missing helper named like a callback is still an error
+    let mut w = hecs::World::new();
+    w.spawn((Time::default(),));
+    let typo = "fn on_start() {\n    on_start_bonus();\n}\n";
+    let script = w.spawn((Script::load("typo.rhai", typo),));
+    Script::system(&mut w);
+    let error = w.get::<Script>(script).unwrap().error.clone();
+    assert!(error.unwrap().starts_with("typo.rhai:2:"));
+}
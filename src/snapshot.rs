@@ -0,0 +1,877 @@
+//! Saves a whole world as text and restores it later, for quicksaves, bug reports and test
+//! fixtures.
+//!
+//! After a header line there is one line per entity: the entity's id followed by its components
+//! as `name=value` (or just `name` for markers), separated by spaces. Wireframes from `SHAPES`
+//! are saved by name and spawners and emitters by their `spawner::Registry` id.
+//!
+//! Left out are `Bot` pilots, the level `Script` along with its `TriggerArea`s, `Messages` and
+//! `Outcome`, and the `Registry` itself. Quickload keeps the registry already loaded.
+
+use crate::assist::Assist;
+use crate::cave::{Cave, CaveChunk};
+use crate::constants::*;
+use crate::fill::Fill;
+use crate::kinematic::{Kinematic, Motion};
+use crate::mortal::{Curve, Fade, Mortal};
+use crate::particles::{Emitter, EmitterDef, Particles, Trigger};
+use crate::pickup::{Fuel, Pickup, PickupDrops, PickupKind, PowerUps};
+use crate::scale::{LineWidth, Scale};
+use crate::score::{Hazard, Match, SpawnPoint};
+use crate::spawner::Spawner;
+use crate::system::*;
+use crate::weapon::{Health, Projectile, Weapon};
+use crate::wind::{WindField, WindStreaks, WindZone};
+use alloc::borrow::Cow;
+use alloc::sync::Arc;
+use core::fmt;
+use hecs::{Entity, EntityBuilder};
+use macroquad::prelude::*;
+use parry2d::math::Point;
+use parry2d::shape::Polyline;
+use std::collections::HashMap;
+
+pub const HEADER: &str = "heli snapshot 1";
+
+/// Wireframes that are saved by name rather than point by point.
+pub const SHAPES: &[(&str, &[(f32, f32)])] = &[
+    ("player", PLAYER_WIREFRAME),
+    ("bounds", BOUNDS_WIREFRAME),
+    ("trash", TRASH_WIREFRAME),
+    ("spark", SPARK_WIREFRAME),
+    ("platform", PLATFORM_WIREFRAME),
+    ("spikes", SPIKES_WIREFRAME),
+    ("pickup", PICKUP_WIREFRAME),
+    ("heavy", HEAVY_WIREFRAME),
+    ("heavy_hull", HEAVY_HULL),
+    ("dart", DART_WIREFRAME),
+];
+
+/// Errors loading snapshots and the data files that configure the game. Each names what the
+/// file holds: "snapshot", "rules", "spawners" or "ships".
+#[derive(Debug, Clone, PartialEq)]
+pub enum DataError {
+    Io(String),
+    /// the first line isn't the header for this kind of file
+    MissingHeader(&'static str),
+    /// file kind, line number, the line's first word
+    UnknownLine(&'static str, usize, String),
+    /// file kind, line number, field name
+    UnknownField(&'static str, usize, String),
+    /// file kind, line number, field name
+    BadValue(&'static str, usize, String),
+    /// snapshot line number, an entity id an earlier line already used
+    DuplicateId(usize, u32),
+}
+
+impl fmt::Display for DataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataError::Io(e) => write!(f, "{}", e),
+            DataError::MissingHeader(file) => write!(f, "not a heli {} file", file),
+            DataError::UnknownLine(file, line, word) => {
+                write!(f, "line {}: {} files have no {} lines", line, file, word)
+            }
+            DataError::UnknownField(file, line, name) => {
+                write!(f, "line {}: unknown {} field {}", line, file, name)
+            }
+            DataError::BadValue(file, line, name) => {
+                write!(f, "line {}: bad value for {} field {}", line, file, name)
+            }
+            DataError::DuplicateId(line, id) => {
+                write!(f, "line {}: entity {} is already in the snapshot", line, id)
+            }
+        }
+    }
+}
+
+/// A text file of game data with built in defaults to fall back on.
+pub trait DataFile: Default {
+    fn load(text: &str) -> Result<Self, DataError>;
+}
+
+/// Loads `path`, or the defaults if there is no such file. A file that fails to load is reported
+/// and the defaults are used instead.
+pub fn load_or_default<T: DataFile>(path: &str) -> T {
+    if !std::path::Path::new(path).exists() {
+        return T::default();
+    }
+    std::fs::read_to_string(path)
+        .map_err(|e| DataError::Io(e.to_string()))
+        .and_then(|text| T::load(&text))
+        .unwrap_or_else(|e| {
+            eprintln!("{}: {}", path, e);
+            T::default()
+        })
+}
+
+pub fn save(w: &hecs::World) -> String {
+    let mut entities: Vec<Entity> = w.query::<()>().iter().map(|(ent, ())| ent).collect();
+    entities.sort_by_key(|ent| ent.id());
+
+    let mut ret = String::from(HEADER);
+    ret.push('\n');
+    for ent in entities {
+        let components = save_entity(w, ent);
+        if components.is_empty() {
+            continue;
+        }
+        ret.push_str(&ent.id().to_string());
+        for c in components {
+            ret.push(' ');
+            ret.push_str(&c);
+        }
+        ret.push('\n');
+    }
+    ret
+}
+
+pub fn load(text: &str) -> Result<hecs::World, DataError> {
+    let mut lines = text.lines().enumerate();
+    match lines.next() {
+        Some((_, HEADER)) => {}
+        _ => return Err(DataError::MissingHeader("snapshot")),
+    }
+    let lines: Vec<(usize, Vec<&str>)> = lines
+        .map(|(i, line)| (i + 1, line.split_whitespace().collect::<Vec<&str>>()))
+        .filter(|(_, words)| !words.is_empty())
+        .collect();
+
+    // entities refer to each other by saved id, so every entity exists before any is filled in
+    let mut w = hecs::World::new();
+    let mut ids: HashMap<u32, Entity> = HashMap::new();
+    for (line, words) in &lines {
+        let id = words[0]
+            .parse()
+            .map_err(|_| DataError::BadValue("snapshot", *line, "id".into()))?;
+        if ids.contains_key(&id) {
+            return Err(DataError::DuplicateId(*line, id));
+        }
+        ids.insert(id, w.spawn(()));
+    }
+
+    for (line, words) in &lines {
+        let ent = ids[&words[0].parse::<u32>().unwrap()];
+        let mut builder = load_entity(*line, &words[1..], &ids)?;
+        w.insert(ent, builder.build()).unwrap();
+    }
+    Ok(w)
+}
+
+pub fn save_file(w: &hecs::World, path: &str) -> Result<(), DataError> {
+    std::fs::write(path, save(w)).map_err(|e| DataError::Io(e.to_string()))
+}
+
+pub fn load_file(path: &str) -> Result<hecs::World, DataError> {
+    let text = std::fs::read_to_string(path).map_err(|e| DataError::Io(e.to_string()))?;
+    load(&text)
+}
+
+fn save_entity(w: &hecs::World, ent: Entity) -> Vec<String> {
+    let mut ret = Vec::new();
+    let mut put = |name: &str, value: String| ret.push(format!("{}={}", name, value));
+
+    if let Ok(c) = w.get::<Pos>(ent) {
+        put("pos", vec(c.0));
+    }
+    if let Ok(c) = w.get::<Vel>(ent) {
+        put("vel", vec(c.0));
+    }
+    if let Ok(c) = w.get::<Rot>(ent) {
+        put("rot", c.0.to_string());
+    }
+    if let Ok(c) = w.get::<RotVel>(ent) {
+        put("rotvel", c.0.to_string());
+    }
+    if let Ok(c) = w.get::<Boost>(ent) {
+        put("boost", c.0.to_string());
+    }
+    if let Ok(c) = w.get::<Color>(ent) {
+        put("color", color(*c));
+    }
+    if let Ok(c) = w.get::<Wireframe>(ent) {
+        put("wireframe", shape(&c.0));
+    }
+    if let Ok(c) = w.get::<Fill>(ent) {
+        put("fill", color(c.color));
+    }
+    if let Ok(c) = w.get::<Collides>(ent) {
+        if let Some(polyline) = c.0.as_shape::<Polyline>() {
+            let vertices: Vec<String> = polyline
+                .vertices()
+                .iter()
+                .map(|p| format!("{},{}", p.x, p.y))
+                .collect();
+            let indices: Vec<String> = polyline
+                .indices()
+                .iter()
+                .map(|[a, b]| format!("{},{}", a, b))
+                .collect();
+            put(
+                "collides",
+                format!("{}/{}", vertices.join(";"), indices.join(";")),
+            );
+        }
+    }
+    if let Ok(c) = w.get::<Scale>(ent) {
+        put("scale", vec(c.size));
+    }
+    if let Ok(c) = w.get::<LineWidth>(ent) {
+        put("linewidth", c.0.to_string());
+    }
+    if let Ok(c) = w.get::<Mortal>(ent) {
+        put("mortal", format!("{},{}", c.born_at, c.erase_at));
+        if let Some(fade) = &c.fade {
+            let (a, s) = (&fade.alpha, &fade.scale);
+            put(
+                "fade",
+                format!(
+                    "{},{},{},{},{},{}",
+                    a.start, a.end, a.power, s.start, s.end, s.power
+                ),
+            );
+        }
+        if let Some(spawner) = &c.on_expire {
+            put("on_expire", spawner.0.to_string());
+        }
+    }
+    if let Ok(c) = w.get::<Emitter>(ent) {
+        put("emitter", c.id.to_string());
+    }
+    if let Ok(c) = w.get::<Kinematic>(ent) {
+        let motion = match &c.motion {
+            Motion::Still => "still".to_string(),
+            Motion::Waypoints { points, speed } => {
+                format!("waypoints:{}:{}", speed, vecs(points))
+            }
+            Motion::Sine { amplitude, period } => format!("sine:{}:{}", vec(*amplitude), period),
+            Motion::Rotate { rate } => format!("rotate:{}", rate),
+        };
+        put(
+            "kinematic",
+            format!("{};{};{}", vec(c.origin), c.start, motion),
+        );
+    }
+    if let Ok(c) = w.get::<WindZone>(ent) {
+        let field = match &c.field {
+            WindField::Constant(v) => format!("constant:{}", vec(*v)),
+            WindField::Vortex { strength } => format!("vortex:{}", strength),
+            WindField::Turbulence {
+                strength,
+                scale,
+                speed,
+            } => format!("turbulence:{},{},{}", strength, scale, speed),
+        };
+        put("wind", format!("{}/{}", vecs(&c.area), field));
+    }
+    if let Ok(c) = w.get::<Assist>(ent) {
+        put("assist", c.name().to_string());
+    }
+    if let Ok(c) = w.get::<Controls>(ent) {
+        if let Some(name) = c.layout_name() {
+            put("controls", name.to_string());
+        }
+    }
+    if let Ok(c) = w.get::<Settings>(ent) {
+        put(
+            "settings",
+            format!(
+                "{},{},{},{},{},{},{},{},{}",
+                c.gravity,
+                c.rotational_acceleration,
+                c.rotational_drag_coefficient,
+                c.drag_coefficient,
+                c.boost_power,
+                c.auto_up_power,
+                c.collision_energy_loss,
+                c.ship_size,
+                c.downwash
+            ),
+        );
+    }
+    if let Ok(c) = w.get::<Time>(ent) {
+        put("time", format!("{},{}", c.now, c.delta));
+    }
+    if let Ok(c) = w.get::<Background>(ent) {
+        put("background", color(c.0));
+    }
+    if let Ok(c) = w.get::<Camera2D>(ent) {
+        put("camera", vec(c.target));
+    }
+    if let Ok(c) = w.get::<Follow>(ent) {
+        put("follow", c.0.id().to_string());
+    }
+    if let Ok(c) = w.get::<Cave>(ent) {
+        put("cave", format!("{},{}", c.seed, c.follow.id()));
+    }
+    if let Ok(c) = w.get::<CaveChunk>(ent) {
+        put("cave_chunk", c.0.to_string());
+    }
+    if let Ok(c) = w.get::<SpawnPoint>(ent) {
+        put("spawn_point", c.0.to_string());
+    }
+    if let Ok(c) = w.get::<Match>(ent) {
+        put("match", c.to_text());
+    }
+    if let Ok(c) = w.get::<Weapon>(ent) {
+        put(
+            "weapon",
+            format!("{},{},{},{}", c.spawner.0, c.cooldown, c.recoil, c.ready_at),
+        );
+    }
+    if let Ok(c) = w.get::<Health>(ent) {
+        put("health", format!("{},{}", c.hp, c.max));
+    }
+    if let Ok(c) = w.get::<Projectile>(ent) {
+        put("projectile", format!("{},{}", c.owner.id(), c.damage));
+    }
+    if let Ok(c) = w.get::<Hull>(ent) {
+        put("hull", shape(&c.0));
+    }
+    if let Ok(c) = w.get::<Overrides>(ent) {
+        let mut c = (*c).clone();
+        let set: Vec<String> = OVERRIDE_NAMES
+            .iter()
+            .filter_map(|name| Some(format!("{}:{}", name, (*c.field(name)?)?)))
+            .collect();
+        put("overrides", set.join(","));
+    }
+    if let Ok(c) = w.get::<Pickup>(ent) {
+        put("pickup", c.0.name().to_string());
+    }
+    if let Ok(c) = w.get::<Fuel>(ent) {
+        put("fuel", format!("{},{}", c.amount, c.max));
+    }
+    if let Ok(c) = w.get::<PowerUps>(ent) {
+        let list: Vec<String> =
+            c.0.iter()
+                .map(|(kind, until)| format!("{}@{}", kind.name(), until))
+                .collect();
+        put("power_ups", list.join(","));
+    }
+    if let Ok(c) = w.get::<PickupDrops>(ent) {
+        put(
+            "pickup_drops",
+            format!(
+                "{}/{}/{},{},{},{},{}",
+                vec(c.center),
+                vec(c.half_size),
+                c.interval,
+                c.max,
+                c.next_at,
+                c.seed,
+                c.dropped
+            ),
+        );
+    }
+
+    let markers = [
+        ("grav", w.get::<Grav>(ent).is_ok()),
+        ("drag", w.get::<Drag>(ent).is_ok()),
+        ("input", w.get::<Input>(ent).is_ok()),
+        ("particles", w.get::<Particles>(ent).is_ok()),
+        ("hazard", w.get::<Hazard>(ent).is_ok()),
+    ];
+    for (name, present) in markers.iter() {
+        if *present {
+            ret.push(name.to_string());
+        }
+    }
+    ret
+}
+
+fn load_entity(
+    line: usize,
+    components: &[&str],
+    ids: &HashMap<u32, Entity>,
+) -> Result<EntityBuilder, DataError> {
+    let mut b = EntityBuilder::new();
+    // fills are triangulated from the wireframe, which may come later on the line
+    let mut wireframe: Option<Cow<'static, [(f32, f32)]>> = None;
+    let mut fill: Option<Color> = None;
+    let mut mortal: Option<Mortal> = None;
+
+    for component in components {
+        let mut split = component.splitn(2, '=');
+        let name = split.next().unwrap();
+        let value = split.next().unwrap_or("");
+        let bad = || DataError::BadValue("snapshot", line, name.to_string());
+        let entity = |s: &str| s.parse::<u32>().ok().and_then(|id| ids.get(&id).cloned());
+
+        match name {
+            "pos" => {
+                b.add(Pos(parse_vec(value).ok_or_else(bad)?));
+            }
+            "vel" => {
+                b.add(Vel(parse_vec(value).ok_or_else(bad)?));
+            }
+            "rot" => {
+                b.add(Rot(value.parse().map_err(|_| bad())?));
+            }
+            "rotvel" => {
+                b.add(RotVel(value.parse().map_err(|_| bad())?));
+            }
+            "boost" => {
+                b.add(Boost(value.parse().map_err(|_| bad())?));
+            }
+            "color" => {
+                b.add(parse_color(value).ok_or_else(bad)?);
+            }
+            "wireframe" => {
+                let shape = parse_shape(value).ok_or_else(bad)?;
+                wireframe = Some(shape.clone());
+                b.add(Wireframe(shape));
+            }
+            "fill" => fill = Some(parse_color(value).ok_or_else(bad)?),
+            "collides" => {
+                let mut parts = value.splitn(2, '/');
+                let vertices = parse_vecs(parts.next().unwrap()).ok_or_else(bad)?;
+                let indices: Vec<[u32; 2]> = parse_list(parts.next().unwrap_or(""), ';', |s| {
+                    let mut ab = s.splitn(2, ',');
+                    Some([ab.next()?.parse().ok()?, ab.next()?.parse().ok()?])
+                })
+                .ok_or_else(bad)?;
+                let vertices = vertices.iter().map(|v| Point::new(v.x, v.y)).collect();
+                b.add(Collides(Box::new(Polyline::new(vertices, Some(indices)))));
+            }
+            "scale" => {
+                b.add(Scale::new(parse_vec(value).ok_or_else(bad)?));
+            }
+            "linewidth" => {
+                b.add(LineWidth(value.parse().map_err(|_| bad())?));
+            }
+            "mortal" => match parse_f64s(value).as_deref() {
+                Some([born, erase]) => {
+                    mortal = Some(Mortal {
+                        erase_at: *erase,
+                        ..Mortal::new(*born, 0.0)
+                    })
+                }
+                _ => return Err(bad()),
+            },
+            "fade" => {
+                let m = mortal.take().ok_or_else(bad)?;
+                mortal = Some(m.with_fade(parse_fade(value).ok_or_else(bad)?));
+            }
+            "on_expire" => {
+                let m = mortal.take().ok_or_else(bad)?;
+                mortal = Some(m.on_expire(Spawner::new(value.to_string())));
+            }
+            "emitter" => {
+                b.add(Emitter::new(value.to_string()));
+            }
+            "kinematic" => {
+                b.add(parse_kinematic(value).ok_or_else(bad)?);
+            }
+            "wind" => {
+                b.add(parse_wind(value).ok_or_else(bad)?);
+                b.add(WindStreaks::default());
+            }
+            "assist" => {
+                let mut assist = Assist::None;
+                for _ in 0..4 {
+                    if assist.name() == value {
+                        break;
+                    }
+                    assist = assist.next();
+                }
+                if assist.name() != value {
+                    return Err(bad());
+                }
+                b.add(assist);
+            }
+            "controls" => {
+                b.add(Controls::from_layout_name(value).ok_or_else(bad)?);
+            }
+            "settings" => {
+                let f = parse_floats(value).ok_or_else(bad)?;
+                if f.len() != 9 {
+                    return Err(bad());
+                }
+                b.add(Settings {
+                    gravity: f[0],
+                    rotational_acceleration: f[1],
+                    rotational_drag_coefficient: f[2],
+                    drag_coefficient: f[3],
+                    boost_power: f[4],
+                    auto_up_power: f[5],
+                    collision_energy_loss: f[6],
+                    ship_size: f[7],
+                    downwash: f[8],
+                });
+            }
+            "time" => {
+                let mut parts = value.splitn(2, ',');
+                let now = parts.next().unwrap().parse().map_err(|_| bad())?;
+                let delta = parts.next().ok_or_else(bad)?;
+                b.add(Time {
+                    now,
+                    delta: delta.parse().map_err(|_| bad())?,
+                });
+            }
+            "background" => {
+                b.add(Background(parse_color(value).ok_or_else(bad)?));
+            }
+            "camera" => {
+                b.add(Camera2D {
+                    target: parse_vec(value).ok_or_else(bad)?,
+                    ..Default::default()
+                });
+            }
+            "follow" => {
+                b.add(Follow(entity(value).ok_or_else(bad)?));
+            }
+            "cave" => {
+                let mut parts = value.splitn(2, ',');
+                let seed = parts.next().unwrap().parse().map_err(|_| bad())?;
+                let follow = parts.next().and_then(entity).ok_or_else(bad)?;
+                b.add(Cave { seed, follow });
+            }
+            "cave_chunk" => {
+                b.add(CaveChunk(value.parse().map_err(|_| bad())?));
+            }
+            "spawn_point" => {
+                b.add(SpawnPoint(value.parse().map_err(|_| bad())?));
+            }
+            "match" => {
+                b.add(Match::parse(value).ok_or_else(bad)?);
+            }
+            "weapon" => {
+                let parts: Vec<&str> = value.split(',').collect();
+                if parts.len() != 4 {
+                    return Err(bad());
+                }
+                b.add(Weapon {
+                    spawner: Spawner::new(parts[0].to_string()),
+                    cooldown: parts[1].parse().map_err(|_| bad())?,
+                    recoil: parts[2].parse().map_err(|_| bad())?,
+                    ready_at: parts[3].parse().map_err(|_| bad())?,
+                });
+            }
+            "health" => {
+                let f = parse_floats(value).ok_or_else(bad)?;
+                if f.len() != 2 {
+                    return Err(bad());
+                }
+                b.add(Health {
+                    hp: f[0],
+                    max: f[1],
+                });
+            }
+            "projectile" => {
+                let mut parts = value.splitn(2, ',');
+                let owner = parts.next().and_then(entity).ok_or_else(bad)?;
+                let damage = parts.next().unwrap_or("").parse().map_err(|_| bad())?;
+                b.add(Projectile { owner, damage });
+            }
+            "hull" => {
+                b.add(Hull(parse_shape(value).ok_or_else(bad)?));
+            }
+            "overrides" => {
+                let mut overrides = Overrides::default();
+                for item in value.split(',').filter(|item| !item.is_empty()) {
+                    let mut split = item.splitn(2, ':');
+                    let name = split.next().unwrap();
+                    let value = split.next().and_then(|v| v.parse().ok()).ok_or_else(bad)?;
+                    if !Overrides::allows(name, value) {
+                        return Err(bad());
+                    }
+                    *overrides.field(name).ok_or_else(bad)? = Some(value);
+                }
+                b.add(overrides);
+            }
+            "pickup" => {
+                b.add(Pickup(PickupKind::from_name(value).ok_or_else(bad)?));
+            }
+            "fuel" => {
+                let f = parse_floats(value).ok_or_else(bad)?;
+                if f.len() != 2 {
+                    return Err(bad());
+                }
+                b.add(Fuel {
+                    amount: f[0],
+                    max: f[1],
+                });
+            }
+            "power_ups" => {
+                let list = parse_list(value, ',', |item| {
+                    let mut split = item.splitn(2, '@');
+                    let kind = PickupKind::from_name(split.next()?)?;
+                    Some((kind, split.next()?.parse().ok()?))
+                })
+                .ok_or_else(bad)?;
+                b.add(PowerUps(list));
+            }
+            "pickup_drops" => {
+                let parts: Vec<&str> = value.split('/').collect();
+                if parts.len() != 3 {
+                    return Err(bad());
+                }
+                let rest: Vec<&str> = parts[2].split(',').collect();
+                if rest.len() != 5 {
+                    return Err(bad());
+                }
+                b.add(PickupDrops {
+                    center: parse_vec(parts[0]).ok_or_else(bad)?,
+                    half_size: parse_vec(parts[1]).ok_or_else(bad)?,
+                    interval: rest[0].parse().map_err(|_| bad())?,
+                    max: rest[1].parse().map_err(|_| bad())?,
+                    next_at: rest[2].parse().map_err(|_| bad())?,
+                    seed: rest[3].parse().map_err(|_| bad())?,
+                    dropped: rest[4].parse().map_err(|_| bad())?,
+                });
+            }
+            "grav" => {
+                b.add(Grav);
+            }
+            "drag" => {
+                b.add(Drag);
+            }
+            "input" => {
+                b.add(Input::default());
+            }
+            "particles" => {
+                b.add(Particles::default());
+            }
+            "hazard" => {
+                b.add(Hazard);
+            }
+            _ => return Err(DataError::UnknownField("snapshot", line, name.to_string())),
+        }
+    }
+
+    if let Some(color) = fill {
+        let bad = || DataError::BadValue("snapshot", line, "fill".into());
+        b.add(Fill::new(&wireframe.ok_or_else(bad)?, color).map_err(|_| bad())?);
+    }
+    if let Some(mortal) = mortal {
+        b.add(mortal);
+    }
+    Ok(b)
+}
+
+/// Fields separated by `;` in declaration order, with the three flags sharing one field. The
+/// gradient is `fraction:r,g,b,a` stops separated by `/`. `push` comes last and may be left off.
+pub(crate) fn parse_emitter(s: &str) -> Option<EmitterDef> {
+    let parts: Vec<&str> = s.split(';').collect();
+    if parts.len() != 12 && parts.len() != 13 {
+        return None;
+    }
+    let trigger = match parts[0] {
+        "boost" => Trigger::Boost,
+        "collision" => Trigger::Collision,
+        "continuous" => Trigger::Continuous,
+        "death" => Trigger::Death,
+        _ => return None,
+    };
+    let pair = |s: &str| match parse_floats(s)?.as_slice() {
+        [a, b] => Some((*a, *b)),
+        _ => None,
+    };
+    let flags = parse_floats(parts[10])?;
+    if flags.len() != 3 {
+        return None;
+    }
+    let gradient = parse_list(parts[11], '/', |stop| {
+        let mut stop = stop.splitn(2, ':');
+        let t = stop.next()?.parse().ok()?;
+        Some((t, parse_color(stop.next()?)?))
+    })?;
+
+    let mut e = EmitterDef::new(trigger);
+    e.rate = parts[1].parse().ok()?;
+    e.burst = parts[2].parse().ok()?;
+    e.offset = parse_vec(parts[3])?;
+    e.direction = parts[4].parse().ok()?;
+    e.spread = parts[5].parse().ok()?;
+    e.speed = pair(parts[6])?;
+    e.lifetime = pair(parts[7])?;
+    e.spin = parts[8].parse().ok()?;
+    e.shape = shape_by_name(parts[9])?;
+    e.inherit_velocity = flags[0] != 0.0;
+    e.grav = flags[1] != 0.0;
+    e.drag = flags[2] != 0.0;
+    e.gradient = Arc::from(gradient);
+    if let Some(push) = parts.get(12) {
+        e.push = push.parse().ok()?;
+    }
+    Some(e)
+}
+
+/// alpha start, end and power then scale start, end and power
+pub(crate) fn parse_fade(s: &str) -> Option<Fade> {
+    match parse_floats(s)?.as_slice() {
+        [a0, a1, ap, s0, s1, sp] => {
+            let curve = |start: f32, end: f32, power: f32| Curve { start, end, power };
+            Some(Fade {
+                alpha: curve(*a0, *a1, *ap),
+                scale: curve(*s0, *s1, *sp),
+            })
+        }
+        _ => None,
+    }
+}
+
+fn parse_kinematic(s: &str) -> Option<Kinematic> {
+    let parts: Vec<&str> = s.splitn(3, ';').collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let mut motion = parts[2].splitn(3, ':');
+    let motion = match motion.next()? {
+        "still" => Motion::Still,
+        "waypoints" => Motion::Waypoints {
+            speed: motion.next()?.parse().ok()?,
+            points: parse_vecs(motion.next()?)?,
+        },
+        "sine" => Motion::Sine {
+            amplitude: parse_vec(motion.next()?)?,
+            period: motion.next()?.parse().ok()?,
+        },
+        "rotate" => Motion::Rotate {
+            rate: motion.next()?.parse().ok()?,
+        },
+        _ => return None,
+    };
+    Some(Kinematic {
+        motion,
+        origin: parse_vec(parts[0])?,
+        start: parts[1].parse().ok()?,
+    })
+}
+
+fn parse_wind(s: &str) -> Option<WindZone> {
+    let mut parts = s.splitn(2, '/');
+    let area = parse_vecs(parts.next()?)?;
+    let mut field = parts.next()?.splitn(2, ':');
+    let kind = field.next()?;
+    let values = parse_floats(field.next()?)?;
+    let field = match (kind, values.as_slice()) {
+        ("constant", [x, y]) => WindField::Constant(vec2(*x, *y)),
+        ("vortex", [strength]) => WindField::Vortex {
+            strength: *strength,
+        },
+        ("turbulence", [strength, scale, speed]) => WindField::Turbulence {
+            strength: *strength,
+            scale: *scale,
+            speed: *speed,
+        },
+        _ => return None,
+    };
+    Some(WindZone::polygon(area, field))
+}
+
+fn shape_name(wireframe: &[(f32, f32)]) -> Option<&'static str> {
+    SHAPES
+        .iter()
+        .find(|(_, shape)| *shape == wireframe)
+        .map(|(name, _)| *name)
+}
+
+pub(crate) fn shape_by_name(name: &str) -> Option<&'static [(f32, f32)]> {
+    SHAPES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, shape)| *shape)
+}
+
+/// A name from `SHAPES` or the points themselves.
+pub(crate) fn shape(wireframe: &[(f32, f32)]) -> String {
+    match shape_name(wireframe) {
+        Some(name) => name.to_string(),
+        None => {
+            let points: Vec<Vec2> = wireframe.iter().map(|(x, y)| vec2(*x, *y)).collect();
+            vecs(&points)
+        }
+    }
+}
+
+pub(crate) fn parse_shape(s: &str) -> Option<Cow<'static, [(f32, f32)]>> {
+    if let Some(shape) = shape_by_name(s) {
+        return Some(shape.into());
+    }
+    let points = parse_vecs(s)?;
+    Some(points.iter().map(|p| (p.x, p.y)).collect::<Vec<_>>().into())
+}
+
+pub(crate) fn vec(v: Vec2) -> String {
+    format!("{},{}", v.x, v.y)
+}
+
+fn vecs(vs: &[Vec2]) -> String {
+    vs.iter().map(|v| vec(*v)).collect::<Vec<_>>().join(";")
+}
+
+pub(crate) fn color(c: Color) -> String {
+    format!("{},{},{},{}", c.r, c.g, c.b, c.a)
+}
+
+pub(crate) fn parse_floats(s: &str) -> Option<Vec<f32>> {
+    s.split(',').map(|f| f.parse().ok()).collect()
+}
+
+fn parse_f64s(s: &str) -> Option<Vec<f64>> {
+    s.split(',').map(|f| f.parse().ok()).collect()
+}
+
+pub(crate) fn parse_vec(s: &str) -> Option<Vec2> {
+    match parse_floats(s)?.as_slice() {
+        [x, y] => Some(vec2(*x, *y)),
+        _ => None,
+    }
+}
+
+fn parse_vecs(s: &str) -> Option<Vec<Vec2>> {
+    parse_list(s, ';', parse_vec)
+}
+
+pub(crate) fn parse_color(s: &str) -> Option<Color> {
+    match parse_floats(s)?.as_slice() {
+        [r, g, b, a] => Some(Color::new(*r, *g, *b, *a)),
+        _ => None,
+    }
+}
+
+/// An empty string is an empty list.
+fn parse_list<T>(s: &str, separator: char, item: impl Fn(&str) -> Option<T>) -> Option<Vec<T>> {
+    if s.is_empty() {
+        return Some(Vec::new());
+    }
+    s.split(separator).map(item).collect()
+}
+
+#[test]
+fn save_load_round_trip() {
+    let mut w = hecs::World::new();
+    w.spawn((Settings::default(),));
+    w.spawn((Time {
+        now: 3.5,
+        delta: 0.1,
+    },));
+    let ship = spawn_ship(&mut w, vec2(10.0, -20.0), RED);
+    w.insert_one(ship, Controls::arrows()).unwrap();
+    w.spawn((Camera2D::default(), Follow(ship)));
+    w.spawn((
+        Pos(vec2(1.0, 2.0)),
+        Wireframe(vec![(0.0, 0.0), (1.0, 0.5), (0.0, 1.0), (0.0, 0.0)].into()),
+        Mortal::new(3.0, 1.0)
+            .with_fade(Fade::vanish())
+            .on_expire(Spawner::new("smoke")),
+    ));
+
+    let saved = save(&w);
+    let loaded = load(&saved).unwrap();
+    assert_eq!(save(&loaded), saved);
+
+    assert_eq!(
+        load("not a snapshot").err(),
+        Some(DataError::MissingHeader("snapshot"))
+    );
+    let unknown = format!("{}\n0 pos=1,2 sparkle\n", HEADER);
+    assert_eq!(
+        load(&unknown).err(),
+        Some(DataError::UnknownField("snapshot", 2, "sparkle".into()))
+    );
+    let twice = format!("{}\n0 pos=1,2\n0 pos=3,4\n", HEADER);
+    assert_eq!(load(&twice).err(), Some(DataError::DuplicateId(3, 0)));
+}
@@ -1,74 +1,343 @@
-use crate::constants::TRASH_WIREFRAME;
-use crate::mortal::Mortal;
-use crate::system::Boost;
-use alloc::sync::Arc;
+use crate::constants::{Overrides, SPARK_WIREFRAME, TRASH_WIREFRAME};
+use crate::mortal::{Curve, Fade, Mortal};
+use crate::particles::EmitterDef;
+use crate::snapshot::{self, DataError, DataFile};
+use crate::system::{Drag, Grav, Pos, Rot, RotVel, Time, Vel, Wireframe};
+use crate::weapon::Projectile;
+use alloc::borrow::Cow;
+use core::f32::consts::{FRAC_PI_2, TAU};
 use hecs::Entity;
 use macroquad::prelude::*;
+use std::collections::HashMap;
+use std::sync::OnceLock;
 
-pub type Spawner = Arc<dyn Fn(&mut hecs::World, Entity) + Send + Sync + 'static>;
+pub const REGISTRY_HEADER: &str = "heli spawners 1";
 
-/// Spawns new entities whenever boosting.
-pub struct BoostToots {
-    pub spawner: Spawner,
-    pub every: f64,
-    pub next_toot: f64,
+/// Refers to a `SpawnerDef` in the `Registry` by id. Ids that aren't registered spawn nothing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spawner(pub Cow<'static, str>);
+
+/// Entities spawned around a source entity, evenly spread around a circle.
+#[derive(Debug, Clone)]
+pub struct SpawnerDef {
+    pub count: usize,
+    /// meters per second, away from the source
+    pub speed: f32,
+    /// fraction of the source's velocity given to each entity
+    pub inherit: f32,
+    /// local space, where entities appear
+    pub offset: Vec2,
+    /// seconds
+    pub lifetime: f32,
+    /// radians per second
+    pub spin: f32,
+    pub color: Color,
+    pub shape: &'static [(f32, f32)],
+    pub grav: bool,
+    /// multiplies gravity for spawned entities, below 1 for floaty debris
+    pub gravity_scale: Option<f32>,
+    pub drag: bool,
+    pub fade: Option<Fade>,
+    /// run as each spawned entity expires
+    pub then: Option<Spawner>,
+    /// spread around the way the source is facing, starting at its nose, rather than around
+    /// the world's x axis
+    pub aim: bool,
+    /// above zero spawned entities are `Projectile`s owned by the source, doing this much damage
+    pub damage: f32,
 }
 
-impl BoostToots {
-    pub fn system(w: &mut hecs::World) {
-        let time = get_time();
+/// Spawners and particle emitters by id. Starts out with the built in ones, more can be loaded
+/// from data.
+#[derive(Debug, Clone)]
+pub struct Registry {
+    pub spawners: HashMap<String, SpawnerDef>,
+    pub emitters: HashMap<String, EmitterDef>,
+}
 
-        let mut emitting: Vec<(Entity, Spawner)> = Default::default();
-        for (ent, (boost, bt)) in w.query_mut::<(&Boost, &mut BoostToots)>() {
-            while bt.next_toot <= time {
-                bt.next_toot += bt.every;
-                if boost.0.abs() > 0.00001 {
-                    emitting.push((ent, bt.spawner.clone()));
-                }
-            }
+impl Spawner {
+    pub fn new(id: impl Into<Cow<'static, str>>) -> Self {
+        Self(id.into())
+    }
+
+    pub fn spawn(&self, w: &mut hecs::World, source: Entity) {
+        if let Some(def) = Registry::spawner(w, &self.0) {
+            def.spawn(w, source);
+        }
+    }
+}
+
+impl Default for SpawnerDef {
+    fn default() -> Self {
+        Self {
+            count: 1,
+            speed: 0.0,
+            inherit: 1.0,
+            offset: vec2(0.0, 0.0),
+            lifetime: 1.0,
+            spin: 0.0,
+            color: WHITE,
+            shape: TRASH_WIREFRAME,
+            grav: false,
+            gravity_scale: None,
+            drag: false,
+            fade: None,
+            then: None,
+            aim: false,
+            damage: 0.0,
+        }
+    }
+}
+
+impl SpawnerDef {
+    /// A ring of short lived sparks that turn to smoke when they burn out.
+    pub fn sparks() -> Self {
+        Self {
+            count: 6,
+            speed: 60.0,
+            lifetime: 0.4,
+            color: Color::new(1.0, 0.7, 0.2, 1.0),
+            shape: SPARK_WIREFRAME,
+            grav: true,
+            drag: true,
+            fade: Some(Fade {
+                alpha: Curve::linear(1.0, 0.6),
+                scale: Curve::linear(1.0, 0.5),
+            }),
+            then: Some(Spawner::new("smoke")),
+            ..Self::default()
         }
+    }
 
-        for (ent, spawner) in emitting {
-            spawner(w, ent);
+    /// A slowly growing, fading puff that drifts with the wind.
+    pub fn smoke() -> Self {
+        Self {
+            inherit: 0.2,
+            lifetime: 1.5,
+            spin: 1.0,
+            color: Color::new(0.3, 0.3, 0.3, 1.0),
+            drag: true,
+            fade: Some(Fade {
+                alpha: Curve::linear(0.6, 0.0),
+                scale: Curve::linear(0.3, 1.5),
+            }),
+            ..Self::default()
+        }
+    }
+
+    /// A fast shot out of the source's nose.
+    pub fn bullet() -> Self {
+        Self {
+            speed: 250.0,
+            offset: vec2(0.0, 12.0),
+            lifetime: 1.5,
+            color: Color::new(1.0, 1.0, 0.6, 1.0),
+            shape: SPARK_WIREFRAME,
+            aim: true,
+            damage: 25.0,
+            ..Self::default()
+        }
+    }
+
+    /// A slow, heavy shot for the heavy ship class.
+    pub fn shell() -> Self {
+        Self {
+            speed: 180.0,
+            offset: vec2(0.0, 12.0),
+            lifetime: 2.0,
+            color: Color::new(1.0, 0.6, 0.2, 1.0),
+            shape: TRASH_WIREFRAME,
+            aim: true,
+            damage: 45.0,
+            ..Self::default()
+        }
+    }
+
+    pub fn spawn(&self, w: &mut hecs::World, source: Entity) {
+        let pos = match w.get::<Pos>(source) {
+            Ok(pos) => pos.0,
+            Err(_) => return,
+        };
+        let vel = w.get::<Vel>(source).map(|v| v.0).unwrap_or(vec2(0.0, 0.0));
+        let rot = w.get::<Rot>(source).map(|r| r.0).unwrap_or(0.0);
+        let offset = Rot(rot).quat().mul_vec3(self.offset.extend(0.0)).truncate();
+        let now = Time::get(w).now;
+
+        // the nose points along local y
+        let start = if self.aim { rot + FRAC_PI_2 } else { 0.0 };
+        for i in 0..self.count {
+            let angle = start + i as f32 * TAU / self.count as f32;
+            let dir = vec2(angle.cos(), angle.sin());
+            let mut mortal = Mortal::new(now, self.lifetime as f64);
+            mortal.fade = self.fade.clone();
+            mortal.on_expire = self.then.clone();
+            let ent = w.spawn((
+                // aimed shapes keep their local y along their flight
+                Rot(if self.aim { angle - FRAC_PI_2 } else { angle }),
+                RotVel(self.spin),
+                Vel(vel * self.inherit + dir * self.speed),
+                Pos(pos + offset),
+                self.color,
+                Wireframe(self.shape.into()),
+                mortal,
+            ));
+            if self.grav {
+                w.insert_one(ent, Grav).unwrap();
+            }
+            if self.drag {
+                w.insert_one(ent, Drag).unwrap();
+            }
+            if self.gravity_scale.is_some() {
+                let overrides = Overrides {
+                    gravity_scale: self.gravity_scale,
+                    ..Overrides::default()
+                };
+                w.insert_one(ent, overrides).unwrap();
+            }
+            if self.damage > 0.0 {
+                let projectile = Projectile {
+                    owner: source,
+                    damage: self.damage,
+                };
+                w.insert_one(ent, projectile).unwrap();
+            }
         }
     }
 }
 
-pub fn firetrail(w: &mut hecs::World, source: Entity) {
-    use crate::system::*;
-    let pos = match get::<Pos>(w, source) {
-        Some(pos) => pos,
-        None => {
-            if cfg!(debug_assertions) {
-                panic!("entity with no position was assigned a firetrail");
+impl Default for Registry {
+    fn default() -> Self {
+        let mut spawners = HashMap::new();
+        spawners.insert("sparks".to_string(), SpawnerDef::sparks());
+        spawners.insert("smoke".to_string(), SpawnerDef::smoke());
+        spawners.insert("bullet".to_string(), SpawnerDef::bullet());
+        spawners.insert("shell".to_string(), SpawnerDef::shell());
+        let mut emitters = HashMap::new();
+        emitters.insert("firetrail".to_string(), EmitterDef::firetrail());
+        emitters.insert("vent".to_string(), EmitterDef::vent());
+        Self { spawners, emitters }
+    }
+}
+
+impl Registry {
+    /// Looks in the world's registry, or the built in one if the world doesn't have a registry.
+    pub fn spawner(w: &hecs::World, id: &str) -> Option<SpawnerDef> {
+        Self::with(w, |registry| registry.spawners.get(id).cloned())
+    }
+
+    /// Looks in the world's registry, or the built in one if the world doesn't have a registry.
+    pub fn emitter(w: &hecs::World, id: &str) -> Option<EmitterDef> {
+        Self::with(w, |registry| registry.emitters.get(id).cloned())
+    }
+
+    /// Runs `f` on the world's registry, or the built in one if the world doesn't have a
+    /// registry. The built in one is only built once.
+    pub fn with<R>(w: &hecs::World, f: impl FnOnce(&Registry) -> R) -> R {
+        static BUILT_IN: OnceLock<Registry> = OnceLock::new();
+        match w.query::<(&Registry,)>().iter().next() {
+            Some((_, (registry,))) => f(registry),
+            None => f(BUILT_IN.get_or_init(Registry::default)),
+        }
+    }
+
+    /// The built in definitions with the ones in `text` added, replacing any with the same id.
+    ///
+    /// After a `REGISTRY_HEADER` line each line is either `spawner <id> <field=value>...` with
+    /// fields named like `SpawnerDef`'s, or `emitter <id> <emitter>` with the emitter's fields
+    /// as `snapshot::parse_emitter` reads them.
+    pub fn load(text: &str) -> Result<Self, DataError> {
+        let mut lines = text.lines().enumerate();
+        match lines.next() {
+            Some((_, REGISTRY_HEADER)) => {}
+            _ => return Err(DataError::MissingHeader("spawners")),
+        }
+
+        let mut ret = Self::default();
+        for (i, line) in lines {
+            let line_number = i + 1;
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                [] => {}
+                ["spawner", id, fields @ ..] => {
+                    let def = parse_spawner(line_number, fields)?;
+                    ret.spawners.insert(id.to_string(), def);
+                }
+                ["emitter", id, emitter] => {
+                    let emitter = snapshot::parse_emitter(emitter).ok_or_else(|| {
+                        DataError::BadValue("spawners", line_number, "emitter".to_string())
+                    })?;
+                    ret.emitters.insert(id.to_string(), emitter);
+                }
+                [kind, ..] => {
+                    return Err(DataError::UnknownLine(
+                        "spawners",
+                        line_number,
+                        kind.to_string(),
+                    ))
+                }
             }
-            return;
         }
+        Ok(ret)
     }
-    .0;
-    let rot: f32 = w.get::<Rot>(source).map(|c| c.0).unwrap_or(0.0);
-    let vel: Vec2 = get::<Vel>(w, source).unwrap_or(Vel(vec2(0.0, 0.0))).0;
-    let color: Color = get::<Color>(w, source).unwrap_or(BLACK);
-    let boost: f32 = get::<Boost>(w, source).unwrap_or(Boost(1.0)).0;
-
-    let q = Rot(rot).quat();
-    let rotate = |v: Vec2| q.mul_vec3(v.extend(0.)).truncate();
-
-    w.spawn((
-        Rot(rot),
-        RotVel(15. * boost.signum()),
-        Vel(vel + rotate(vec2(0.0, -200.0)) * boost.signum()),
-        Pos(pos + rotate(vec2(0.0, -10.0)) * boost.signum()),
-        Grav,
-        Drag,
-        color,
-        Wireframe(TRASH_WIREFRAME),
-        Mortal {
-            erase_at: get_time() + 2.0,
-        },
-    ));
 }
 
-fn get<T: Clone + Send + Sync + 'static>(w: &hecs::World, ent: Entity) -> Option<T> {
-    w.get::<T>(ent).ok().map(|c| -> T { (&c as &T).clone() })
+impl DataFile for Registry {
+    fn load(text: &str) -> Result<Self, DataError> {
+        Registry::load(text)
+    }
+}
+
+fn parse_spawner(line: usize, fields: &[&str]) -> Result<SpawnerDef, DataError> {
+    let mut def = SpawnerDef::default();
+    for field in fields {
+        let mut split = field.splitn(2, '=');
+        let name = split.next().unwrap();
+        let value = split.next().unwrap_or("");
+        let bad = || DataError::BadValue("spawners", line, name.to_string());
+        match name {
+            "count" => def.count = value.parse().map_err(|_| bad())?,
+            "speed" => def.speed = value.parse().map_err(|_| bad())?,
+            "inherit" => def.inherit = value.parse().map_err(|_| bad())?,
+            "offset" => def.offset = snapshot::parse_vec(value).ok_or_else(bad)?,
+            "lifetime" => def.lifetime = value.parse().map_err(|_| bad())?,
+            "spin" => def.spin = value.parse().map_err(|_| bad())?,
+            "color" => def.color = snapshot::parse_color(value).ok_or_else(bad)?,
+            "shape" => def.shape = snapshot::shape_by_name(value).ok_or_else(bad)?,
+            "grav" => def.grav = true,
+            "gravity_scale" => def.gravity_scale = Some(value.parse().map_err(|_| bad())?),
+            "drag" => def.drag = true,
+            "fade" => def.fade = Some(snapshot::parse_fade(value).ok_or_else(bad)?),
+            "then" => def.then = Some(Spawner::new(value.to_string())),
+            "aim" => def.aim = true,
+            "damage" => def.damage = value.parse().map_err(|_| bad())?,
+            _ => return Err(DataError::UnknownField("spawners", line, name.to_string())),
+        }
+    }
+    Ok(def)
+}
+
+#[test]
+fn registry_loads_over_builtins() {
+    let text = format!(
+        "{}\n\nspawner embers count=3 speed=20 lifetime=2 shape=spark grav then=smoke\n",
+        REGISTRY_HEADER
+    );
+    let registry = Registry::load(&text).unwrap();
+    let embers = &registry.spawners["embers"];
+    assert_eq!(embers.count, 3);
+    assert!(embers.grav && !embers.drag);
+    assert_eq!(embers.then, Some(Spawner::new("smoke")));
+    assert!(registry.spawners.contains_key("sparks"));
+    assert!(registry.emitters.contains_key("firetrail"));
+
+    let typo = format!("{}\nspawner embers cuont=3\n", REGISTRY_HEADER);
+    assert_eq!(
+        Registry::load(&typo).err(),
+        Some(DataError::UnknownField("spawners", 2, "cuont".into()))
+    );
+    let stray = format!("{}\nspawn embers count=3\n", REGISTRY_HEADER);
+    assert_eq!(
+        Registry::load(&stray).err(),
+        Some(DataError::UnknownLine("spawners", 2, "spawn".into()))
+    );
 }
@@ -0,0 +1,454 @@
+//! Watching a game from another window or machine. The game runs a `SpectatorServer` that sends
+//! every connected `Spectator` the `Pos`, `Rot`, `Color`, `Wireframe`, `Scale` and `LineWidth`
+//! of whatever it draws, only sending what changed since the last frame. Particles, `Fill` and
+//! the fade of dying `Mortal`s aren't sent. Spectators keep player 1 centered, the way the
+//! game's camera does.
+//!
+//! The stream is text, one change per line, ending each frame with `end`:
+//!
+//! ```text
+//! pos 3 12.5,-40
+//! rot 3 0.25
+//! color 3 1,0,0,1
+//! wireframe 3 player
+//! scale 3 1.5,1.5
+//! line_width 3 1
+//! follow 3
+//! gone 7
+//! end
+//! ```
+
+use crate::constants::{color_pallet, TRASH_WIREFRAME, WORLD_HEIGHT};
+use crate::scale::{LineWidth, Scale};
+use crate::snapshot;
+use crate::system::{draw_wireframe, ships, Pos, Rot, Wireframe};
+use hecs::Entity;
+use macroquad::prelude::*;
+use std::collections::HashMap;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+
+/// fields sent for each entity, in this order
+const FIELDS: [&str; 6] = ["pos", "rot", "color", "wireframe", "scale", "line_width"];
+
+/// bytes, a spectator that falls this far behind is dropped
+const MAX_QUEUED: usize = 1 << 20;
+
+/// Sends the world to spectators. Call `publish` once per frame.
+pub struct SpectatorServer {
+    listener: TcpListener,
+    clients: Vec<Client>,
+}
+
+struct Client {
+    stream: TcpStream,
+    /// what this client has been sent
+    known: View,
+    /// frames the socket hasn't taken yet, oldest first
+    queued: Vec<u8>,
+}
+
+/// What spectators are shown of a world.
+#[derive(Debug, Clone, Default)]
+struct View {
+    /// `FIELDS` by entity id
+    entities: HashMap<u32, [String; 6]>,
+    /// id of the entity the camera follows
+    follow: Option<u32>,
+}
+
+impl SpectatorServer {
+    pub fn bind(addr: SocketAddr) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            clients: Vec::new(),
+        })
+    }
+
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn clients(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Takes in new spectators and sends everyone what changed.
+    pub fn publish(&mut self, w: &hecs::World) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    let setup = stream
+                        .set_nonblocking(true)
+                        .and_then(|()| stream.set_nodelay(true));
+                    match setup {
+                        Ok(()) => self.clients.push(Client {
+                            stream,
+                            known: View::default(),
+                            queued: Vec::new(),
+                        }),
+                        Err(e) => eprintln!("spectator: {}", e),
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    eprintln!("spectator: {}", e);
+                    break;
+                }
+            }
+        }
+        if self.clients.is_empty() {
+            return;
+        }
+
+        let current = visible(w);
+        let mut dropped = Vec::new();
+        for (i, client) in self.clients.iter_mut().enumerate() {
+            client
+                .queued
+                .extend_from_slice(delta(&client.known, &current).as_bytes());
+            client.known = current.clone();
+            match client.flush() {
+                Ok(()) if client.queued.len() > MAX_QUEUED => {
+                    eprintln!("spectator dropped: too far behind");
+                    dropped.push(i);
+                }
+                Ok(()) => {}
+                Err(e) => {
+                    eprintln!("spectator dropped: {}", e);
+                    dropped.push(i);
+                }
+            }
+        }
+        for i in dropped.into_iter().rev() {
+            self.clients.remove(i);
+        }
+    }
+}
+
+impl Client {
+    /// Writes as much of the queue as the socket takes without blocking.
+    fn flush(&mut self) -> std::io::Result<()> {
+        let mut written = 0;
+        while written < self.queued.len() {
+            match self.stream.write(&self.queued[written..]) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(len) => written += len,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.queued.drain(..written);
+        Ok(())
+    }
+}
+
+/// Every drawn entity's fields, written the way snapshots write them.
+fn visible(w: &hecs::World) -> View {
+    let entities = w
+        .query::<(
+            &Pos,
+            &Rot,
+            &Color,
+            &Wireframe,
+            Option<&Scale>,
+            Option<&LineWidth>,
+        )>()
+        .iter()
+        .map(|(ent, (pos, rot, color, wireframe, scale, line_width))| {
+            let fields = [
+                snapshot::vec(pos.0),
+                rot.0.to_string(),
+                snapshot::color(*color),
+                snapshot::shape(&wireframe.0),
+                snapshot::vec(scale.map(|s| s.size).unwrap_or(vec2(1.0, 1.0))),
+                line_width.map(|lw| lw.0).unwrap_or(1.0).to_string(),
+            ];
+            (ent.id(), fields)
+        })
+        .collect();
+    View {
+        entities,
+        follow: ships(w).first().map(|ent| ent.id()),
+    }
+}
+
+/// Lines turning `old` into `new`, ending with `end`.
+fn delta(old: &View, new: &View) -> String {
+    let (old_entities, new_entities) = (&old.entities, &new.entities);
+    let mut ids: Vec<&u32> = new_entities.keys().collect();
+    ids.sort();
+    let mut ret = String::new();
+    for id in ids {
+        let fields = &new_entities[id];
+        for (i, value) in fields.iter().enumerate() {
+            if old_entities.get(id).is_none_or(|known| known[i] != *value) {
+                ret.push_str(&format!("{} {} {}\n", FIELDS[i], id, value));
+            }
+        }
+    }
+    if new.follow != old.follow {
+        if let Some(id) = new.follow {
+            ret.push_str(&format!("follow {}\n", id));
+        }
+    }
+    let mut gone: Vec<&u32> = old_entities
+        .keys()
+        .filter(|id| !new_entities.contains_key(id))
+        .collect();
+    gone.sort();
+    for id in gone {
+        ret.push_str(&format!("gone {}\n", id));
+    }
+    ret.push_str("end\n");
+    ret
+}
+
+/// A read only view of a game being played elsewhere.
+pub struct Spectator {
+    stream: TcpStream,
+    /// received but not yet a whole frame
+    pending: String,
+    /// what the game is drawing, entities are spawned here as the server mentions them
+    world: hecs::World,
+    /// server ids to our entities
+    ids: HashMap<u32, Entity>,
+    /// server id of the entity to keep centered
+    follow: Option<u32>,
+    /// set once the server goes away
+    pub error: Option<String>,
+}
+
+impl Spectator {
+    pub fn connect(addr: SocketAddr) -> std::io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nonblocking(true)?;
+        Ok(Self {
+            stream,
+            pending: String::new(),
+            world: hecs::World::new(),
+            ids: HashMap::new(),
+            follow: None,
+            error: None,
+        })
+    }
+
+    pub fn world(&self) -> &hecs::World {
+        &self.world
+    }
+
+    /// The entity the camera keeps centered, player 1 in the game being watched.
+    pub fn followed(&self) -> Option<Entity> {
+        self.follow.and_then(|id| self.ids.get(&id).cloned())
+    }
+
+    /// Applies every whole frame received so far. Returns how many there were.
+    pub fn update(&mut self) -> usize {
+        let mut buf = [0u8; 4096];
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => {
+                    self.error.get_or_insert_with(|| "game ended".to_string());
+                    break;
+                }
+                Ok(len) => self.pending.push_str(&String::from_utf8_lossy(&buf[..len])),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    self.error = Some(e.to_string());
+                    break;
+                }
+            }
+        }
+
+        let mut frames = 0;
+        loop {
+            let end = if self.pending.starts_with("end\n") {
+                0
+            } else {
+                match self.pending.find("\nend\n") {
+                    Some(i) => i + 1,
+                    None => break,
+                }
+            };
+            let frame: String = self.pending.drain(..end + 4).collect();
+            for line in frame.lines() {
+                if let Err(e) = self.apply(line) {
+                    self.error = Some(e);
+                }
+            }
+            frames += 1;
+        }
+        frames
+    }
+
+    fn apply(&mut self, line: &str) -> Result<(), String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let bad = || format!("bad line from server: {}", line);
+        let (kind, id, value) = match words.as_slice() {
+            ["end"] => return Ok(()),
+            [kind, id] => (*kind, id.parse::<u32>().map_err(|_| bad())?, ""),
+            [kind, id, value] => (*kind, id.parse::<u32>().map_err(|_| bad())?, *value),
+            _ => return Err(bad()),
+        };
+        match kind {
+            "gone" => {
+                if let Some(ent) = self.ids.remove(&id) {
+                    self.world.despawn(ent).unwrap();
+                }
+                return Ok(());
+            }
+            "follow" => {
+                self.follow = Some(id);
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        // placeholders until the rest of the entity's fields arrive, later in the same frame
+        let world = &mut self.world;
+        let ent = *self.ids.entry(id).or_insert_with(|| {
+            world.spawn((
+                Pos(vec2(0.0, 0.0)),
+                Rot(0.0),
+                WHITE,
+                Wireframe(TRASH_WIREFRAME.into()),
+                Scale::uniform(1.0),
+                LineWidth(1.0),
+            ))
+        });
+        match kind {
+            "pos" => {
+                self.world.get_mut::<Pos>(ent).unwrap().0 =
+                    snapshot::parse_vec(value).ok_or_else(bad)?
+            }
+            "rot" => {
+                self.world.get_mut::<Rot>(ent).unwrap().0 = value.parse().map_err(|_| bad())?
+            }
+            "color" => {
+                *self.world.get_mut::<Color>(ent).unwrap() =
+                    snapshot::parse_color(value).ok_or_else(bad)?
+            }
+            "wireframe" => {
+                self.world.get_mut::<Wireframe>(ent).unwrap().0 =
+                    snapshot::parse_shape(value).ok_or_else(bad)?
+            }
+            "scale" => {
+                self.world.get_mut::<Scale>(ent).unwrap().size =
+                    snapshot::parse_vec(value).ok_or_else(bad)?
+            }
+            "line_width" => {
+                self.world.get_mut::<LineWidth>(ent).unwrap().0 =
+                    value.parse().map_err(|_| bad())?
+            }
+            _ => return Err(bad()),
+        }
+        Ok(())
+    }
+
+    pub fn draw(&self) {
+        clear_background(color_pallet()[3]);
+        let target = self
+            .followed()
+            .and_then(|ent| self.world.get::<Pos>(ent).ok().map(|p| p.0 / WORLD_HEIGHT))
+            .unwrap_or(vec2(0.0, 0.0));
+        set_camera(&Camera2D {
+            zoom: vec2(1., screen_width() / screen_height()),
+            target,
+            ..Default::default()
+        });
+        for (_, (pos, rot, color, wireframe, scale, line_width)) in self
+            .world
+            .query::<(&Pos, &Rot, &Color, &Wireframe, &Scale, &LineWidth)>()
+            .iter()
+        {
+            draw_wireframe(
+                &wireframe.0,
+                pos.0,
+                rot.quat(),
+                scale.size,
+                line_width.0,
+                *color,
+            );
+        }
+
+        set_default_camera();
+        let status = match &self.error {
+            Some(e) => e.as_str(),
+            None => "spectating",
+        };
+        draw_text(status, 10.0, 30.0, 30.0, WHITE);
+    }
+}
+
+#[test]
+fn spectator_follows_changes() {
+    use crate::constants::PLAYER_WIREFRAME;
+    use crate::system::Input;
+
+    // stands in for a running game
+    let mut game = hecs::World::new();
+    let ship = game.spawn((
+        Pos(vec2(1.0, 2.0)),
+        Rot(0.5),
+        RED,
+        Wireframe(PLAYER_WIREFRAME.into()),
+        Scale::uniform(2.0),
+        Input::default(),
+    ));
+    let debris = game.spawn((
+        Pos(vec2(-3.0, 0.0)),
+        Rot(0.0),
+        WHITE,
+        Wireframe(TRASH_WIREFRAME.into()),
+    ));
+    let mut server = SpectatorServer::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let mut spectator = Spectator::connect(server.local_addr().unwrap()).unwrap();
+
+    fn sync(server: &mut SpectatorServer, spectator: &mut Spectator, w: &hecs::World) {
+        server.publish(w);
+        for _ in 0..1000 {
+            if spectator.update() > 0 {
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        panic!("nothing from the server");
+    }
+    // the first publish may come before the connection is accepted
+    while server.clients() == 0 {
+        server.publish(&game);
+    }
+    sync(&mut server, &mut spectator, &game);
+    let seen = |spectator: &Spectator| {
+        let mut seen: Vec<(Vec2, f32)> = spectator
+            .world()
+            .query::<(&Pos, &Rot)>()
+            .iter()
+            .map(|(_, (p, r))| (p.0, r.0))
+            .collect();
+        seen.sort_by(|a, b| a.0.x.partial_cmp(&b.0.x).unwrap());
+        seen
+    };
+    assert_eq!(
+        seen(&spectator),
+        vec![(vec2(-3.0, 0.0), 0.0), (vec2(1.0, 2.0), 0.5)]
+    );
+
+    game.get_mut::<Pos>(ship).unwrap().0 = vec2(4.0, 2.0);
+    game.despawn(debris).unwrap();
+    sync(&mut server, &mut spectator, &game);
+    assert_eq!(seen(&spectator), vec![(vec2(4.0, 2.0), 0.5)]);
+    let followed = spectator.followed().unwrap();
+    assert_eq!(
+        spectator.world().get::<Pos>(followed).unwrap().0,
+        vec2(4.0, 2.0)
+    );
+    assert_eq!(
+        spectator.world().get::<Scale>(followed).unwrap().size,
+        vec2(2.0, 2.0)
+    );
+    assert_eq!(spectator.error, None);
+}
@@ -1,10 +1,26 @@
+use crate::assist::Assist;
+use crate::cave::{center_line, Cave};
 use crate::constants::*;
+use crate::debug::DebugOverlay;
+use crate::fill::Fill;
+use crate::inspector::Inspector;
+use crate::kinematic::Kinematic;
+use crate::level::Level;
+use crate::menu::{Mode, Opponent, Options};
 use crate::mortal::Mortal;
-use crate::spawner::firetrail;
-use crate::spawner::BoostToots;
+use crate::particles::{Emitter, Particles};
+use crate::pickup::{Fuel, Pickup, PowerUps};
+use crate::pilot::{Bot, Course};
+use crate::scale::{LineWidth, Scale};
+use crate::schedule::{Profiler, Schedule, Stage};
+use crate::score::{Match, Rules};
+use crate::script::{Messages, Script};
+use crate::snapshot;
+use crate::spawner::Registry;
 use crate::util::wireframe_to_polyline;
-use alloc::sync::Arc;
-use core::f32::consts::{PI, TAU};
+use crate::weapon::{Health, Projectile, Weapon};
+use crate::wind::{WindZone, Winds};
+use alloc::borrow::Cow;
 use core::fmt::Debug;
 use hecs::Entity;
 use macroquad::prelude::*;
@@ -15,114 +31,236 @@ use parry2d::shape::Shape;
 
 pub struct Heli {
     world: hecs::World,
+    schedule: Schedule,
+    /// frame time not yet simulated, always less than `FIXED_STEP` after `fixed_steps`
+    accumulator: f32,
+}
+
+/// seconds simulated by each `Heli::step` from `Heli::update`, the same on every machine so
+/// networked games stay in sync
+pub const FIXED_STEP: f32 = 1.0 / 60.0;
+
+/// after a long stall the simulation falls behind rather than spending seconds catching up
+const MAX_STEPS_PER_FRAME: usize = 5;
+
+/// written with F5, read back with F9
+const QUICKSAVE: &str = "quicksave.heli";
+
+/// extra spawner and emitter definitions, loaded at startup if present
+const SPAWNERS: &str = "spawners.txt";
+
+/// match rules for the arena, the defaults are used if missing
+const RULES: &str = "rules.txt";
+
+/// Hash of the data files a game loads, so networked peers can check they play by the same
+/// rules. A missing file hashes like an empty one.
+pub fn data_hash() -> u32 {
+    [SPAWNERS, RULES]
+        .iter()
+        .flat_map(|path| {
+            let mut text = std::fs::read(path).unwrap_or_default();
+            // keeps "ab" + "" apart from "a" + "b"
+            text.push(0);
+            text
+        })
+        .fold(0x811c_9dc5u32, |h, b| {
+            (h ^ b as u32).wrapping_mul(0x0100_0193)
+        })
 }
 
 impl Heli {
-    pub fn new() -> Self {
+    pub fn new(options: &Options) -> Self {
         let mut world = hecs::World::new();
 
-        let camera = (Camera2D::default(),);
-        world.spawn(camera);
+        world.spawn((snapshot::load_or_default::<Registry>(SPAWNERS),));
 
         // player 1
-        world.spawn((
-            Controls {
-                up: KeyCode::Up,
-                down: KeyCode::Down,
-                left: KeyCode::Left,
-                right: KeyCode::Right,
-            },
-            Rot(0.),
-            RotVel(0.),
-            Vel(vec2(0., 0.)),
-            Pos(vec2(0.0, 0.0)),
-            Grav,
-            Drag,
-            Boost(0.0),
-            color_pallet()[0],
-            Collides(Box::new(wireframe_to_polyline(PLAYER_WIREFRAME))),
-            Wireframe(PLAYER_WIREFRAME),
-            BoostToots {
-                spawner: Arc::new(firetrail),
-                every: 1.0 / 60.,
-                next_toot: get_time(),
-            },
-        ));
+        let player_1 = spawn_ship(&mut world, vec2(0.0, 0.0), color_pallet()[0]);
+        world.insert_one(player_1, Controls::arrows()).unwrap();
 
         // player 2
-        world.spawn((
-            Controls {
-                up: KeyCode::W,
-                down: KeyCode::S,
-                left: KeyCode::A,
-                right: KeyCode::D,
-            },
-            Rot(0.),
-            RotVel(0.),
-            Vel((0., 0.).into()),
-            Pos(vec2(PLAYER_SIZE, PLAYER_SIZE)),
-            Grav,
-            Drag,
-            Boost(0.0),
+        let player_2 = spawn_ship(
+            &mut world,
+            vec2(PLAYER_SIZE, PLAYER_SIZE),
             color_pallet()[1],
-            Collides(Box::new(wireframe_to_polyline(PLAYER_WIREFRAME))),
-            Wireframe(PLAYER_WIREFRAME),
-            BoostToots {
-                spawner: Arc::new(firetrail),
-                every: 1.0 / 60.,
-                next_toot: get_time(),
-            },
-        ));
-
-        let walls = (
-            Collides(Box::new(wireframe_to_polyline(BOUNDS_WIREFRAME))),
-            Wireframe(BOUNDS_WIREFRAME),
-            Pos(vec2(0., 0.)),
-            Vel(vec2(0., 0.)),
-            Rot(0.),
-            color_pallet()[2],
         );
-        world.spawn(walls);
+
+        let course = match options.mode {
+            Mode::Arena => {
+                let level = Level::default();
+                world.spawn((Camera2D::default(),));
+                if let Err(e) = level.spawn(&mut world) {
+                    panic!("default level failed to load: {}", e);
+                }
+                let rules = snapshot::load_or_default::<Rules>(RULES);
+                world.spawn((Match::new(rules, 2, 0.0),));
+                Course {
+                    points: level.race_line,
+                    looped: true,
+                }
+            }
+            Mode::Cave { seed } => {
+                world.spawn((Camera2D::default(), Follow(player_1)));
+                world.spawn((Cave {
+                    seed,
+                    follow: player_1,
+                },));
+                Course {
+                    points: center_line(seed),
+                    looped: false,
+                }
+            }
+        };
+
+        for (i, ship) in [player_1, player_2].iter().enumerate() {
+            let class = options.ships.get(i).cloned().unwrap_or_default();
+            class.apply(&mut world, *ship);
+        }
+
+        match options.opponent {
+            Opponent::Human => world.insert_one(player_2, Controls::wasd()),
+            Opponent::Bot(kind) => world.insert_one(player_2, Bot::new(kind, &course)),
+        }
+        .unwrap();
 
         world.spawn((Settings::default(),));
         world.spawn((Background(color_pallet()[3]),));
+        world.spawn((Time::default(),));
+        world.spawn((Particles::default(),));
+
+        Self::from_world(world)
+    }
 
-        Self { world }
+    /// Wraps a world built elsewhere, for example by `env::Env`. The world should contain a
+    /// `Time`.
+    pub fn from_world(world: hecs::World) -> Self {
+        Self {
+            world,
+            schedule: default_schedule(),
+            accumulator: 0.0,
+        }
+    }
+
+    pub fn world(&self) -> &hecs::World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut hecs::World {
+        &mut self.world
+    }
+
+    /// Add systems here rather than to `step`.
+    pub fn schedule_mut(&mut self) -> &mut Schedule {
+        &mut self.schedule
     }
 
     pub fn update(&mut self) {
-        self.controls();
-        self.collision();
-        self.newtonian();
-        BoostToots::system(&mut self.world);
-        Mortal::system(&mut self.world);
+        self.read_keyboard();
+        for _ in 0..self.fixed_steps() {
+            self.step(FIXED_STEP);
+        }
         self.msc();
     }
 
+    /// How many `FIXED_STEP`s of frame time have built up since the last call.
+    pub fn fixed_steps(&mut self) -> usize {
+        self.accumulator += get_frame_time();
+        let steps = (self.accumulator / FIXED_STEP) as usize;
+        self.accumulator -= steps as f32 * FIXED_STEP;
+        if steps > MAX_STEPS_PER_FRAME {
+            self.accumulator = 0.0;
+        }
+        steps.min(MAX_STEPS_PER_FRAME)
+    }
+
+    /// Advances the simulation by `delta_t` seconds. Doesn't touch the window, keyboard or wall
+    /// clock so it can run headless.
+    pub fn step(&mut self, delta_t: f32) {
+        for (_, (time,)) in self.world.query_mut::<(&mut Time,)>() {
+            time.now += delta_t as f64;
+            time.delta = delta_t;
+        }
+
+        self.schedule.run(&mut self.world);
+    }
+
     pub fn ui(&mut self) {
         let ui: &mut Ui = &mut root_ui();
 
         for (_, (settings,)) in self.world.query::<(&mut Settings,)>().iter() {
             Window::new(hash!(), vec2(10.0, 40.0), vec2(400.0, 500.0)).ui(ui, |ui| settings.ui(ui));
         }
+
+        Inspector::ui(&mut self.world, ui);
+        if Profiler::enabled(&self.world) {
+            self.schedule.ui(ui);
+        }
     }
 
     pub fn draw(&self) {
         clear_background(self.get_one::<Background>().unwrap_or(Background(GRAY)).0);
         for (_, (camera,)) in self.world.query::<(&Camera2D,)>().iter() {
             set_camera(camera);
-            for (_, (c, p, r, w)) in self
+            let time = self.time().now;
+            // fills first so outlines stay visible
+            for (_, (f, p, r, m, s)) in self
+                .world
+                .query::<(&Fill, &Pos, &Rot, Option<&Mortal>, Option<&Scale>)>()
+                .iter()
+            {
+                let (alpha, fade_scale) = m.map(|m| m.visual(time)).unwrap_or((1.0, 1.0));
+                let scale = s.map(|s| s.size).unwrap_or(vec2(1.0, 1.0)) * fade_scale;
+                f.draw(p.0, r.quat(), scale, alpha);
+            }
+            for (_, (c, p, r, w, m, s, lw)) in self
                 .world
-                .query::<(&Color, &Pos, &Rot, &Wireframe)>()
+                .query::<(
+                    &Color,
+                    &Pos,
+                    &Rot,
+                    &Wireframe,
+                    Option<&Mortal>,
+                    Option<&Scale>,
+                    Option<&LineWidth>,
+                )>()
                 .iter()
             {
                 let q = r.quat();
-                draw_wireframe(w.0, p.0, q, *c);
+                let (alpha, fade_scale) = m.map(|m| m.visual(time)).unwrap_or((1.0, 1.0));
+                let color = Color {
+                    a: c.a * alpha,
+                    ..*c
+                };
+                let scale = s.map(|s| s.size).unwrap_or(vec2(1.0, 1.0)) * fade_scale;
+                let line_width = lw.map(|lw| lw.0).unwrap_or(1.0);
+                draw_wireframe(&w.0, p.0, q, scale, line_width, color);
             }
+            Particles::draw(&self.world);
+            WindZone::draw(&self.world);
+            if DebugOverlay::enabled(&self.world) {
+                DebugOverlay::draw_world(&self.world);
+            }
+            Script::draw_world(&self.world);
+            Health::draw_world(&self.world);
+            PowerUps::draw_world(&self.world);
+            Inspector::draw_world(&self.world);
         }
 
         set_default_camera();
+        if DebugOverlay::enabled(&self.world) {
+            DebugOverlay::draw_panel(&self.world);
+        }
+        Script::draw_hud(&self.world);
+        Match::draw_hud(&self.world);
+        PowerUps::draw_hud(&self.world);
         draw_text(&format!("fps: {}", get_fps()), 10.0, 30.0, 30.0, WHITE);
+        let mut y = 60.0;
+        for (_, (_controls, assist, color)) in
+            self.world.query::<(&Controls, &Assist, &Color)>().iter()
+        {
+            draw_text(&format!("assist: {}", assist.name()), 10.0, y, 30.0, *color);
+            y += 30.0;
+        }
     }
 
     pub fn should_quit(&self) -> bool {
@@ -140,55 +278,63 @@ impl Heli {
             .map(|(_, (t,))| t.clone())
     }
 
-    fn get_settings(&mut self) -> Settings {
-        let count = self.world.query_mut::<(&Settings,)>().into_iter().count();
-        assert!(count <= 1);
-        self.world
-            .query_mut::<(&Settings,)>()
-            .into_iter()
-            .next()
-            .map(|(_, (s,))| s.clone())
-            .unwrap_or_default()
+    fn time(&self) -> Time {
+        Time::get(&self.world)
     }
 }
 
 impl Heli {
-    fn controls(&mut self) {
-        let delta_t = get_frame_time();
-        let settings = self.get_settings();
+    fn read_keyboard(&mut self) {
+        // bots fill in their own input
+        for (_id, (controls, input)) in self.world.query_mut::<(&Controls, &mut Input)>() {
+            *input = controls.read();
+        }
 
-        // boost
-        for (_id, (controls, Boost(b))) in self.world.query_mut::<(&Controls, &mut Boost)>() {
-            *b = 0.0;
-            if is_key_down(controls.up) {
-                *b += settings.boost_power;
-            }
-            if is_key_down(controls.down) {
-                *b -= settings.boost_power;
+        for (_id, (controls, assist)) in self.world.query_mut::<(&Controls, &mut Assist)>() {
+            if is_key_pressed(controls.assist) {
+                *assist = assist.next();
             }
         }
 
-        // rotation accel
-        for (_id, (controls, RotVel(rv))) in self.world.query_mut::<(&Controls, &mut RotVel)>() {
-            if is_key_down(controls.left) {
-                *rv += settings.rotational_acceleration * delta_t;
+        if is_key_pressed(KeyCode::F1) {
+            DebugOverlay::toggle(&mut self.world);
+        }
+        if is_key_pressed(KeyCode::F2) {
+            Inspector::toggle(&mut self.world);
+        }
+        if is_key_pressed(KeyCode::F3) {
+            Profiler::toggle(&mut self.world);
+        }
+        if is_key_pressed(KeyCode::Enter) {
+            Match::rematch(&mut self.world);
+        }
+
+        if is_key_pressed(KeyCode::F5) {
+            if let Err(e) = snapshot::save_file(&self.world, QUICKSAVE) {
+                eprintln!("quicksave failed: {}", e);
             }
-            if is_key_down(controls.right) {
-                *rv -= settings.rotational_acceleration * delta_t;
+        }
+        if is_key_pressed(KeyCode::F9) {
+            match snapshot::load_file(QUICKSAVE) {
+                Ok(mut world) => {
+                    // definitions aren't part of the save, keep the ones already loaded
+                    let registry = self.get_one::<Registry>().unwrap_or_default();
+                    world.spawn((registry,));
+                    self.world = world;
+                }
+                Err(e) => eprintln!("quickload failed: {}", e),
             }
         }
 
-        // when boosting up, rotation should tend upwards, it feels better that way
-        for (_id, (_controls, rv, r, b)) in self
-            .world
-            .query_mut::<(&Controls, &mut RotVel, &Rot, &Boost)>()
+        // select by clicking in the world, clicks on windows are for the windows
+        let mouse: Vec2 = mouse_position().into();
+        if Inspector::enabled(&self.world)
+            && is_mouse_button_pressed(MouseButton::Left)
+            && !root_ui().is_mouse_over(mouse)
         {
-            if b.0 >= 0.1 {
-                let r = (r.0 + PI).rem_euclid(TAU) - PI;
-                debug_assert!(r >= -PI - 0.0001);
-                debug_assert!(r <= PI + 0.0001);
-                let rotvel_delta = -r * settings.auto_up_power * delta_t;
-                rv.0 += rotvel_delta;
+            if let Some(camera) = self.get_one::<Camera2D>() {
+                let point = camera.screen_to_world(mouse) * WORLD_HEIGHT;
+                Inspector::pick(&mut self.world, point);
             }
         }
 
@@ -198,16 +344,70 @@ impl Heli {
         }
     }
 
-    fn collision(&mut self) {
-        let delta_t = get_frame_time();
-        let settings = self.get_settings();
+    fn controls(w: &mut hecs::World) {
+        let delta_t = Time::get(w).delta;
+        let settings = Settings::get(w);
+
+        Bot::system(w);
+
+        // boost
+        for (_id, (input, Boost(b), overrides, power_ups)) in
+            w.query_mut::<(&Input, &mut Boost, Option<&Overrides>, Option<&PowerUps>)>()
+        {
+            let boost_power = settings.local(overrides, power_ups).boost_power;
+            *b = 0.0;
+            if input.up {
+                *b += boost_power;
+            }
+            if input.down {
+                *b -= boost_power;
+            }
+        }
+
+        // rotation accel
+        for (_id, (input, RotVel(rv), overrides)) in
+            w.query_mut::<(&Input, &mut RotVel, Option<&Overrides>)>()
+        {
+            let acceleration = settings.local(overrides, None).rotational_acceleration;
+            if input.left {
+                *rv += acceleration * delta_t;
+            }
+            if input.right {
+                *rv -= acceleration * delta_t;
+            }
+        }
+
+        Assist::system(w);
+
+        // charged for the boost assist settled on, assist can thrust without input
+        for (_id, (Boost(b), fuel)) in w.query_mut::<(&mut Boost, &mut Fuel)>() {
+            *b = fuel.burn(*b, delta_t);
+        }
+    }
+
+    fn collision(w: &mut hecs::World) {
+        let delta_t = Time::get(w).delta;
+        let settings = Settings::get(w);
 
-        let mut collisions: Vec<(Entity, Entity, TOI, f32)> = Vec::new();
-        for (ia, (Vel(va), Pos(pa), Collides(ca), Rot(ra))) in
-            self.world.query::<(&Vel, &Pos, &Collides, &Rot)>().iter()
+        let mut collisions: Vec<(Entity, Entity, TOI, f32, Vec2, bool)> = Vec::new();
+        for (ia, (Vel(va), Pos(pa), Collides(ca), Rot(ra), ka)) in w
+            .query::<(&Vel, &Pos, &Collides, &Rot, Option<&Kinematic>)>()
+            .iter()
         {
-            for (ib, (Vel(vb), Pos(pb), Collides(cb), Rot(rb))) in
-                self.world.query::<(&Vel, &Pos, &Collides, &Rot)>().iter()
+            if ka.is_some() {
+                // kinematic bodies follow their script, collisions don't move them
+                continue;
+            }
+            for (ib, (Vel(vb), Pos(pb), Collides(cb), Rot(rb), rvb, kb)) in w
+                .query::<(
+                    &Vel,
+                    &Pos,
+                    &Collides,
+                    &Rot,
+                    Option<&RotVel>,
+                    Option<&Kinematic>,
+                )>()
+                .iter()
             {
                 if ia == ib {
                     continue;
@@ -226,7 +426,14 @@ impl Heli {
                     None => {}
                     Some(toi) => {
                         debug_assert!(toi.status != TOIStatus::Failed);
-                        collisions.push((ia, ib, toi, *ra));
+                        // velocity of the surface of b at the point of contact, includes spin
+                        let r = Rot(*rb)
+                            .quat()
+                            .mul_vec3(vec3(toi.witness2.x, toi.witness2.y, 0.0))
+                            .truncate();
+                        let spin = rvb.map(|rv| rv.0).unwrap_or(0.0);
+                        let surface_vel = *vb + vec2(-r.y, r.x) * spin;
+                        collisions.push((ia, ib, toi, *ra, surface_vel, kb.is_some()));
                     }
                 }
             }
@@ -237,9 +444,10 @@ impl Heli {
                 .unwrap()
         });
         collisions.dedup_by(|a, b| (a.0, a.1) == (b.0, b.1));
+        let mut contacts: Vec<Contact> = Vec::new();
         for (
             ia,
-            _ib,
+            ib,
             TOI {
                 toi,
                 witness1,
@@ -249,10 +457,12 @@ impl Heli {
                 status: _,
             },
             ra,
+            other_vel,
+            other_kinematic,
         ) in collisions
         {
-            let (vel, pos, rvel) = self
-                .world
+            let exchange_share = 2.0 * mass_of(w, ib) / (mass_of(w, ia) + mass_of(w, ib));
+            let (vel, pos, rvel) = w
                 .query_one_mut::<(&mut Vel, &mut Pos, Option<&mut RotVel>)>(ia)
                 .unwrap();
             let v: &mut Vec2 = &mut vel.0;
@@ -266,15 +476,35 @@ impl Heli {
 
             // perhaps if this ends up being janky, you can use witness1 to calculate normal
 
-            if v.dot(n) <= 0.0 {
+            // everything below works relative to the other body so moving platforms
+            // push and carry whatever they touch
+            let relative = *v - other_vel;
+
+            if relative.dot(n) <= 0.0 {
                 // velocity is already pointing away from normal
                 // no need to bounce
                 continue;
             }
 
+            let witness = Rot(ra)
+                .quat()
+                .mul_vec3(vec3(witness1.x, witness1.y, 0.0))
+                .truncate();
+            contacts.push(Contact {
+                a: ia,
+                b: ib,
+                point: *p + witness,
+                normal: n,
+                speed: relative.dot(n),
+                own_speed: v.dot(n),
+            });
+
             // reflect velocity according to normal
             // https://www.youtube.com/watch?v=naaeH1qbjdQ
-            let newvel = *v - v.dot(n) * n * 2.0;
+            // kinematic bodies can't be budged so we bounce fully off of them,
+            // other bodies share the exchange by mass, half each when they weigh the same
+            let exchange = if other_kinematic { 2.0 } else { exchange_share };
+            let newvel = other_vel + relative - relative.dot(n) * n * exchange;
 
             // position is moved into the collision such that the next time velocity is applied
             // position will be outside of the collision
@@ -283,74 +513,206 @@ impl Heli {
 
             rvel.0 += rotvel_delta_on_impact(
                 Vec2::new(witness1.x, witness1.y),
-                Vec2::new(normal1.x, normal1.y) * v.length(),
+                Vec2::new(normal1.x, normal1.y) * relative.length(),
             );
 
             *v = newvel;
 
             // but wait, there's more. we now want to lose some energy
+            // energy is lost relative to the surface we hit
             *p = *p + *v * delta_t; // move pos to where we know it will be at end of tick
-            *v = *v * (1.0 - settings.collision_energy_loss);
+            *v = other_vel + (*v - other_vel) * (1.0 - settings.collision_energy_loss);
             *p = *p - *v * delta_t; // move p back so that is properly placed at end of tick
         }
 
+        let mut contacts = Some(contacts);
+        if let Some((_, (c,))) = w.query_mut::<(&mut Contacts,)>().into_iter().next() {
+            c.0 = contacts.take().unwrap();
+        }
+        if let Some(contacts) = contacts {
+            w.spawn((Contacts(contacts),));
+        }
+
         // collision with air, also known as drag
-        let drag_mult = delta_t * settings.drag_coefficient;
-        debug_assert!(drag_mult < 1.0);
-        for (_i, (v, Drag)) in self.world.query_mut::<(&mut Vel, &Drag)>() {
-            v.0 -= v.0 * drag_mult;
+        // drag is relative to the air, so wind zones push things along
+        let winds = Winds::collect(w);
+        let time = Time::get(w).now;
+        for (_i, (v, p, Drag, overrides)) in
+            w.query_mut::<(&mut Vel, Option<&Pos>, &Drag, Option<&Overrides>)>()
+        {
+            let drag_mult = delta_t * settings.local(overrides, None).drag_coefficient;
+            debug_assert!(drag_mult < 1.0);
+            let air = p
+                .map(|p| winds.air_velocity(p.0, time))
+                .unwrap_or(vec2(0.0, 0.0));
+            v.0 -= (v.0 - air) * drag_mult;
         }
 
         // rotational drag
-        let rdrag_mult = delta_t * settings.rotational_drag_coefficient;
-        debug_assert!(rdrag_mult < 1.0);
-        for (_i, (rv, Drag)) in self.world.query_mut::<(&mut RotVel, &Drag)>() {
+        for (_i, (rv, Drag, overrides)) in w.query_mut::<(&mut RotVel, &Drag, Option<&Overrides>)>()
+        {
+            let rdrag_mult = delta_t * settings.local(overrides, None).rotational_drag_coefficient;
+            debug_assert!(rdrag_mult < 1.0);
             rv.0 -= rv.0 * rdrag_mult;
         }
     }
 
-    fn newtonian(&mut self) {
-        let delta_t = get_frame_time();
-        let settings = self.get_settings();
+    fn newtonian(w: &mut hecs::World) {
+        let delta_t = Time::get(w).delta;
+        let settings = Settings::get(w);
 
         // apply velocity to position
-        for (_id, (v, p)) in self.world.query_mut::<(&Vel, &mut Pos)>() {
+        for (_id, (v, p)) in w.query_mut::<(&Vel, &mut Pos)>() {
             p.0 += v.0 * delta_t;
         }
 
         // apply gravity to velocity
-        for (_id, (Grav, v)) in self.world.query_mut::<(&Grav, &mut Vel)>() {
-            v.0.y += settings.gravity * delta_t;
+        for (_id, (Grav, v, overrides, power_ups)) in
+            w.query_mut::<(&Grav, &mut Vel, Option<&Overrides>, Option<&PowerUps>)>()
+        {
+            let gravity = settings.local(overrides, power_ups).gravity;
+            v.0.y += gravity * delta_t;
         }
 
         // apply boost to velocity
-        for (_id, (b, r, v)) in self.world.query_mut::<(&Boost, &Rot, &mut Vel)>() {
+        for (_id, (b, r, v)) in w.query_mut::<(&Boost, &Rot, &mut Vel)>() {
             v.0 += r.quat().mul_vec3(Vec3::Y * b.0 * delta_t).truncate();
         }
 
         // apply rotational velocity to rotation
-        for (_id, (rv, r)) in self.world.query_mut::<(&RotVel, &mut Rot)>() {
+        for (_id, (rv, r)) in w.query_mut::<(&RotVel, &mut Rot)>() {
             r.0 += rv.0 * delta_t;
         }
     }
 
-    fn msc(&mut self) {
+    /// Fits cameras to the window and keeps followed entities centered.
+    pub fn msc(&mut self) {
         // maintain aspect ratio
         for (_, (camera,)) in self.world.query_mut::<(&mut Camera2D,)>() {
             camera.zoom = vec2(1., screen_width() / screen_height());
         }
+
+        // keep followed entities centered
+        for (_, (camera, follow)) in self.world.query::<(&mut Camera2D, &Follow)>().iter() {
+            if let Ok(p) = self.world.get::<Pos>(follow.0) {
+                camera.target = p.0 / WORLD_HEIGHT;
+            }
+        }
     }
 }
 
-#[derive(Debug)]
+/// Everything `Heli::step` runs.
+fn default_schedule() -> Schedule {
+    let mut schedule = Schedule::default();
+    schedule
+        .add("controls", Stage::Input, Heli::controls)
+        .add("weapons", Stage::Input, Weapon::system)
+        .after("controls")
+        .add("scale", Stage::Physics, Scale::system)
+        .add("kinematic", Stage::Physics, Kinematic::system)
+        .add("collision", Stage::Physics, Heli::collision)
+        .after("scale")
+        .after("kinematic")
+        .add("projectiles", Stage::Physics, Projectile::system)
+        .after("collision")
+        .add("newtonian", Stage::Physics, Heli::newtonian)
+        .after("collision")
+        .after("projectiles")
+        .add("wind", Stage::Physics, WindZone::system)
+        .after("newtonian")
+        .add("cave", Stage::Gameplay, Cave::system)
+        .add("emitters", Stage::Gameplay, Emitter::system)
+        .add("particles", Stage::Gameplay, Particles::system)
+        .after("emitters")
+        .add("downwash", Stage::Gameplay, Particles::downwash)
+        .after("particles")
+        .add("pickups", Stage::Gameplay, Pickup::system)
+        .add("script", Stage::Gameplay, Script::system)
+        .add("match", Stage::Gameplay, Match::system)
+        .add("mortal", Stage::Cleanup, Mortal::system)
+        .add("messages", Stage::Cleanup, Messages::system);
+    schedule
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Controls {
     up: KeyCode,
     down: KeyCode,
     left: KeyCode,
     right: KeyCode,
+    fire: KeyCode,
+    /// cycles through flight assist modes
+    assist: KeyCode,
 }
 
-#[derive(Debug)]
+impl Controls {
+    /// arrow keys, right control fires, right shift cycles assist
+    pub fn arrows() -> Self {
+        Self {
+            up: KeyCode::Up,
+            down: KeyCode::Down,
+            left: KeyCode::Left,
+            right: KeyCode::Right,
+            fire: KeyCode::RightControl,
+            assist: KeyCode::RightShift,
+        }
+    }
+
+    /// WASD, space fires, left shift cycles assist
+    pub fn wasd() -> Self {
+        Self {
+            up: KeyCode::W,
+            down: KeyCode::S,
+            left: KeyCode::A,
+            right: KeyCode::D,
+            fire: KeyCode::Space,
+            assist: KeyCode::LeftShift,
+        }
+    }
+
+    /// The keys held right now.
+    pub fn read(&self) -> Input {
+        Input {
+            up: is_key_down(self.up),
+            down: is_key_down(self.down),
+            left: is_key_down(self.left),
+            right: is_key_down(self.right),
+            fire: is_key_down(self.fire),
+        }
+    }
+
+    /// "arrows" or "wasd", None for any other layout
+    pub fn layout_name(&self) -> Option<&'static str> {
+        if *self == Self::arrows() {
+            Some("arrows")
+        } else if *self == Self::wasd() {
+            Some("wasd")
+        } else {
+            None
+        }
+    }
+
+    pub fn from_layout_name(name: &str) -> Option<Self> {
+        match name {
+            "arrows" => Some(Self::arrows()),
+            "wasd" => Some(Self::wasd()),
+            _ => None,
+        }
+    }
+}
+
+/// What a pilot is asking the ship to do this frame, written by `Controls` for humans
+/// and by `Bot` for computer pilots.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Input {
+    pub up: bool,
+    pub down: bool,
+    pub left: bool,
+    pub right: bool,
+    pub fire: bool,
+}
+
+#[derive(Debug, Clone)]
 pub struct Rot(pub f32);
 
 impl Rot {
@@ -359,7 +721,7 @@ impl Rot {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct RotVel(pub f32);
 
 #[derive(Debug, Clone)]
@@ -368,10 +730,10 @@ pub struct Vel(pub Vec2);
 #[derive(Debug, Clone)]
 pub struct Pos(pub Vec2);
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Grav;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Drag;
 
 #[derive(Debug, Clone)]
@@ -380,23 +742,119 @@ pub struct Boost(pub f32);
 #[derive(Debug)]
 pub struct Quit;
 
+/// Every bounce from the latest tick, written by `Heli::collision`.
+#[derive(Debug, Default)]
+pub struct Contacts(pub Vec<Contact>);
+
+#[derive(Debug, Clone)]
+pub struct Contact {
+    /// the entity that bounced
+    pub a: Entity,
+    /// what it bounced off of
+    pub b: Entity,
+    /// world space
+    pub point: Vec2,
+    /// world space, points from a toward b
+    pub normal: Vec2,
+    /// meters per second, closing speed along the normal
+    pub speed: f32,
+    /// meters per second, how fast a itself was moving along the normal
+    pub own_speed: f32,
+}
+
+/// World clock, seconds since the world was created. Advanced by `Heli::step` so nothing in the
+/// simulation depends on wall time.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Time {
+    pub now: f64,
+    /// length of the current tick
+    pub delta: f32,
+}
+
+impl Time {
+    pub fn get(w: &hecs::World) -> Time {
+        w.query::<(&Time,)>()
+            .iter()
+            .next()
+            .map(|(_, (t,))| *t)
+            .unwrap_or_default()
+    }
+}
+
 pub struct Collides(pub Box<dyn Shape>);
 
-#[derive(Debug)]
-pub struct Wireframe(pub &'static [(f32, f32)]);
+#[derive(Debug, Clone)]
+/// Line segments connecting each point to the next. Usually one of the consts in `constants`,
+/// owned when generated at runtime.
+pub struct Wireframe(pub Cow<'static, [(f32, f32)]>);
+
+/// The outline `Scale` builds `Collides` from, when it shouldn't be the `Wireframe`.
+#[derive(Debug, Clone)]
+pub struct Hull(pub Cow<'static, [(f32, f32)]>);
 
 #[derive(Debug, Clone)]
 pub struct Background(pub Color);
 
-fn draw_wireframe(wireframe: &[(f32, f32)], position: Vec2, rotation: Quat, color: Color) {
+/// Camera component, keeps the entity centered on screen.
+#[derive(Debug)]
+pub struct Follow(pub Entity);
+
+/// Player ships, the entities pilots steer, in the order they were spawned. Snapshots keep
+/// entities in id order so the order survives a save and load.
+pub fn ships(w: &hecs::World) -> Vec<Entity> {
+    let mut ret: Vec<Entity> = w.query::<(&Input,)>().iter().map(|(ent, _)| ent).collect();
+    ret.sort_by_key(|ent| ent.id());
+    ret
+}
+
+/// Spawns a ship with no pilot. Add `Controls` or a `Bot` to fly it. Its exhaust is the
+/// `Registry`'s "firetrail" emitter.
+pub fn spawn_ship(world: &mut hecs::World, pos: Vec2, color: Color) -> Entity {
+    let ship = world.spawn((
+        Input::default(),
+        Rot(0.),
+        RotVel(0.),
+        Vel(vec2(0., 0.)),
+        Pos(pos),
+        Grav,
+        Drag,
+        Boost(0.0),
+        Scale::uniform(1.0),
+        Assist::AutoLevel,
+        color,
+        Collides(Box::new(wireframe_to_polyline(PLAYER_WIREFRAME))),
+        Wireframe(PLAYER_WIREFRAME.into()),
+    ));
+    // hecs bundles stop at 15 components
+    world
+        .insert(
+            ship,
+            (
+                Emitter::new("firetrail"),
+                Health::new(SHIP_HEALTH),
+                Weapon::gun(),
+            ),
+        )
+        .unwrap();
+    ship
+}
+
+pub fn draw_wireframe(
+    wireframe: &[(f32, f32)],
+    position: Vec2,
+    rotation: Quat,
+    scale: Vec2,
+    line_width_meters: f32,
+    color: Color,
+) {
     debug_assert!(!wireframe.is_empty());
     // the screen is 2 units tall (-1.0 to 1.0)
     // the world is WORLD_HEIGHT meters wide
     let meters_per_screen = 1.0 / WORLD_HEIGHT;
-    let line_width_meters = 1.0;
 
     let to_screen = |point: (f32, f32)| {
         let mut p: Vec2 = point.into();
+        p *= scale;
         p = rotation.mul_vec3(p.extend(0.0)).truncate();
         p += position;
         p *= meters_per_screen;
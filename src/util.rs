@@ -1,4 +1,5 @@
 use macroquad::color::Color;
+use macroquad::math::Vec2;
 use parry2d::math::Point;
 use parry2d::shape::Polyline;
 
@@ -17,3 +18,102 @@ pub fn wireframe_to_polyline(wf: &[(f32, f32)]) -> Polyline {
         None,
     )
 }
+
+/// Like `wireframe_to_polyline` but stretched along x and y.
+pub fn scaled_polyline(wf: &[(f32, f32)], scale: Vec2) -> Polyline {
+    Polyline::new(
+        wf.iter()
+            .cloned()
+            .map(|(x, y)| Point::new(x * scale.x, y * scale.y))
+            .collect(),
+        None,
+    )
+}
+
+/// Even-odd point in polygon test. The polygon may be given open or closed.
+pub fn polygon_contains(polygon: &[Vec2], point: Vec2) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len().wrapping_sub(1);
+    for i in 0..polygon.len() {
+        let (a, b) = (polygon[i], polygon[j]);
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < (b.x - a.x) * (point.y - a.y) / (b.y - a.y) + a.x
+        {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+/// Smooth 2d value noise in the range 0.0..1.0. The same seed and coordinates always produce
+/// the same value.
+pub fn value_noise(seed: u32, x: f32, y: f32) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+    let (sx, sy) = (smooth(x - x0), smooth(y - y0));
+    let (ix, iy) = (x0 as i32, y0 as i32);
+
+    let a = lattice(seed, ix, iy);
+    let b = lattice(seed, ix + 1, iy);
+    let c = lattice(seed, ix, iy + 1);
+    let d = lattice(seed, ix + 1, iy + 1);
+
+    let top = a + (b - a) * sx;
+    let bottom = c + (d - c) * sx;
+    top + (bottom - top) * sy
+}
+
+fn lattice(seed: u32, x: i32, y: i32) -> f32 {
+    let mut h = seed
+        ^ (x as u32).wrapping_mul(0x8da6_b343)
+        ^ (y as u32).wrapping_mul(0xd816_3841);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0x5bd1_e995);
+    h ^= h >> 15;
+    h as f32 / u32::MAX as f32
+}
+
+/// Small deterministic random number generator (xorshift64*), for anything that must replay
+/// identically from a seed.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // state must never be zero
+        Self(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.0 = x;
+        x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// uniform in low..high
+    pub fn range(&mut self, low: f32, high: f32) -> f32 {
+        let unit = (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32;
+        low + (high - low) * unit
+    }
+}
+
+#[test]
+fn scaled_polyline_bounds() {
+    use parry2d::shape::Shape;
+    let aabb = scaled_polyline(&[(-1.0, -1.0), (1.0, 1.0)], Vec2::new(2.0, 3.0)).compute_local_aabb();
+    assert_eq!((aabb.mins.x, aabb.mins.y), (-2.0, -3.0));
+    assert_eq!((aabb.maxs.x, aabb.maxs.y), (2.0, 3.0));
+}
+
+#[test]
+fn noise_is_deterministic() {
+    for &(x, y) in &[(0.0, 0.0), (1.5, -3.25), (100.1, 7.7)] {
+        let n = value_noise(7, x, y);
+        assert_eq!(n, value_noise(7, x, y));
+        assert!((0.0..=1.0).contains(&n));
+    }
+}
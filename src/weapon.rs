@@ -0,0 +1,247 @@
+use crate::commands::Commands;
+use crate::constants::{mass_of, WORLD_HEIGHT};
+use crate::pickup::{PickupKind, PowerUps};
+use crate::spawner::{Registry, Spawner};
+use crate::system::{Collides, Input, Pos, Rot, Time, Vel};
+use hecs::Entity;
+use macroquad::prelude::*;
+use parry2d::math::{Isometry, Vector};
+use parry2d::query::time_of_impact;
+use parry2d::shape::Ball;
+
+/// Fires whatever its spawner spawns while `Input::fire` is held, at most once per cooldown.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Weapon {
+    /// should spawn entities with a `Projectile`
+    pub spawner: Spawner,
+    /// seconds between shots
+    pub cooldown: f32,
+    /// meters per second each shot pushes back a shooter of mass 1
+    pub recoil: f32,
+    /// world time the next shot can be fired
+    pub ready_at: f64,
+}
+
+/// Flies until it hits something it can collide with, then damages it and disappears.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Projectile {
+    /// never hit, so shots don't hit the ship firing them
+    pub owner: Entity,
+    /// taken from the `Health` of whatever is hit
+    pub damage: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Health {
+    pub hp: f32,
+    pub max: f32,
+}
+
+/// Every projectile hit from the latest tick, written by `Projectile::system`.
+#[derive(Debug, Default)]
+pub struct Hits(pub Vec<Hit>);
+
+#[derive(Debug, Clone)]
+pub struct Hit {
+    /// who fired
+    pub owner: Entity,
+    /// what was hit
+    pub target: Entity,
+    /// world space
+    pub point: Vec2,
+    pub damage: f32,
+    /// this hit took the target's last health
+    pub fatal: bool,
+}
+
+/// meters, projectiles collide as a ball this size
+const PROJECTILE_RADIUS: f32 = 1.0;
+
+impl Weapon {
+    /// Fires `bullet`s from the built in `Registry`.
+    pub fn gun() -> Self {
+        Self {
+            spawner: Spawner::new("bullet"),
+            cooldown: 0.25,
+            recoil: 4.0,
+            ready_at: 0.0,
+        }
+    }
+
+    pub fn system(w: &mut hecs::World) {
+        let now = Time::get(w).now;
+        let mut firing: Vec<(Entity, Spawner)> = Vec::new();
+        for (ent, (weapon, input)) in w.query_mut::<(&mut Weapon, &Input)>() {
+            if input.fire && now >= weapon.ready_at {
+                weapon.ready_at = now + weapon.cooldown as f64;
+                firing.push((ent, weapon.spawner.clone()));
+            }
+        }
+
+        for (ent, spawner) in firing {
+            spawner.spawn(w, ent);
+            let recoil = w.get::<Weapon>(ent).unwrap().recoil / mass_of(w, ent);
+            if let Ok((v, r)) = w.query_one_mut::<(&mut Vel, &Rot)>(ent) {
+                let nose = r.quat().mul_vec3(Vec3::Y).truncate();
+                v.0 -= nose * recoil;
+            }
+        }
+    }
+}
+
+impl Projectile {
+    /// Sweeps each projectile along this tick's motion so fast shots can't pass through thin
+    /// walls, should run before positions are integrated. Hits go in `Hits`.
+    pub fn system(w: &mut hecs::World) {
+        let delta_t = Time::get(w).delta;
+        let ball = Ball::new(PROJECTILE_RADIUS);
+
+        let mut hits: Vec<(Entity, Hit)> = Vec::new();
+        for (ip, (projectile, Pos(pp), Vel(vp))) in w.query::<(&Projectile, &Pos, &Vel)>().iter() {
+            let mut first: Option<(f32, Entity, Vec2)> = None;
+            for (ib, (Collides(cb), Pos(pb), Rot(rb), vb)) in
+                w.query::<(&Collides, &Pos, &Rot, Option<&Vel>)>().iter()
+            {
+                if ib == projectile.owner || ib == ip {
+                    continue;
+                }
+                let vb = vb.map(|v| v.0).unwrap_or(vec2(0.0, 0.0));
+                let impact = time_of_impact(
+                    &Isometry::translation(pp.x, pp.y),
+                    &Vector::new(vp.x, vp.y),
+                    &ball,
+                    &Isometry::new([pb.x, pb.y].into(), *rb),
+                    &Vector::new(vb.x, vb.y),
+                    &**cb,
+                    delta_t,
+                )
+                .unwrap();
+                if let Some(toi) = impact {
+                    if first.is_none_or(|(t, _, _)| toi.toi < t) {
+                        first = Some((toi.toi, ib, *pp + *vp * toi.toi));
+                    }
+                }
+            }
+            if let Some((_, target, point)) = first {
+                let hit = Hit {
+                    owner: projectile.owner,
+                    target,
+                    point,
+                    damage: projectile.damage,
+                    fatal: false,
+                };
+                hits.push((ip, hit));
+            }
+        }
+
+        let mut commands = Commands::default();
+        let mut events = Vec::new();
+        for (projectile, mut hit) in hits {
+            let shielded = w
+                .get::<PowerUps>(hit.target)
+                .is_ok_and(|p| p.has(PickupKind::Shield));
+            if shielded {
+                hit.damage = 0.0;
+            }
+            if let Ok(mut health) = w.get_mut::<Health>(hit.target) {
+                let was_alive = health.hp > 0.0;
+                health.hp = (health.hp - hit.damage).max(0.0);
+                hit.fatal = was_alive && health.hp <= 0.0;
+            }
+            if let Some(def) = Registry::spawner(w, "sparks") {
+                *w.get_mut::<Pos>(projectile).unwrap() = Pos(hit.point);
+                *w.get_mut::<Vel>(projectile).unwrap() = Vel(vec2(0.0, 0.0));
+                def.spawn(w, projectile);
+            }
+            commands.despawn(projectile);
+            events.push(hit);
+        }
+        commands.submit(w);
+
+        let mut events = Some(events);
+        if let Some((_, (h,))) = w.query_mut::<(&mut Hits,)>().into_iter().next() {
+            h.0 = events.take().unwrap();
+        }
+        if let Some(events) = events {
+            w.spawn((Hits(events),));
+        }
+    }
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { hp: max, max }
+    }
+
+    /// Bars under damaged entities. Expects the world camera to be set.
+    pub fn draw_world(w: &hecs::World) {
+        let meters_per_screen = 1.0 / WORLD_HEIGHT;
+        let (width, height) = (16.0, 2.0);
+        for (_, (health, pos)) in w.query::<(&Health, &Pos)>().iter() {
+            if health.hp >= health.max {
+                continue;
+            }
+            let corner = (pos.0 - vec2(width / 2.0, 14.0)) * meters_per_screen;
+            let filled = width * (health.hp / health.max).max(0.0);
+            draw_rectangle(
+                corner.x,
+                corner.y,
+                width * meters_per_screen,
+                height * meters_per_screen,
+                Color::new(0.0, 0.0, 0.0, 0.5),
+            );
+            draw_rectangle(
+                corner.x,
+                corner.y,
+                filled * meters_per_screen,
+                height * meters_per_screen,
+                RED,
+            );
+        }
+    }
+}
+
+#[test]
+fn fast_shot_hits_thin_wall() {
+    use crate::system::spawn_ship;
+    use crate::util::wireframe_to_polyline;
+
+    let mut w = hecs::World::new();
+    w.spawn((Time {
+        now: 0.0,
+        delta: 0.1,
+    },));
+    let ship = spawn_ship(&mut w, vec2(0.0, 0.0), WHITE);
+    let wall = [(-0.5, -50.0), (-0.5, 50.0)];
+    let target = w.spawn((
+        Pos(vec2(100.0, 0.0)),
+        Rot(0.0),
+        Collides(Box::new(wireframe_to_polyline(&wall))),
+        Health::new(30.0),
+    ));
+    // travels 1000 meters this tick, far past the wall
+    let shot = w.spawn((
+        Pos(vec2(0.0, 0.0)),
+        Vel(vec2(10_000.0, 0.0)),
+        Projectile {
+            owner: ship,
+            damage: 40.0,
+        },
+    ));
+
+    Projectile::system(&mut w);
+    Commands::apply(&mut w);
+    assert!(!w.contains(shot));
+    assert_eq!(w.get::<Health>(target).unwrap().hp, 0.0);
+    let health = (*w.get::<Health>(ship).unwrap()).clone();
+    assert_eq!(health.hp, health.max);
+    let hits = w
+        .query::<(&Hits,)>()
+        .iter()
+        .next()
+        .map(|(_, (h,))| h.0.clone())
+        .unwrap();
+    assert_eq!(hits.len(), 1);
+    assert!(hits[0].fatal && hits[0].owner == ship);
+    assert!((hits[0].point.x - 98.5).abs() < 0.1);
+}
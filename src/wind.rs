@@ -0,0 +1,195 @@
+use crate::constants::WORLD_HEIGHT;
+use crate::system::{Pos, Time};
+use crate::util::{polygon_contains, value_noise};
+use core::f32::consts::TAU;
+use macroquad::prelude::*;
+
+/// A region of moving air. The area is a polygon relative to the entity's `Pos`.
+#[derive(Debug, Clone)]
+pub struct WindZone {
+    pub area: Vec<Vec2>,
+    pub field: WindField,
+}
+
+#[derive(Debug, Clone)]
+pub enum WindField {
+    /// meters per second
+    Constant(Vec2),
+    /// air circles the zone's position, counterclockwise for positive strength
+    /// meters per second
+    Vortex { strength: f32 },
+    /// air moves in smoothly varying directions
+    Turbulence {
+        /// meters per second
+        strength: f32,
+        /// meters, size of a single gust
+        scale: f32,
+        /// how quickly the gusts change over time
+        speed: f32,
+    },
+}
+
+/// Visualizes the zone with short lines carried by the wind.
+#[derive(Debug, Default)]
+pub struct WindStreaks(Vec<Streak>);
+
+#[derive(Debug)]
+struct Streak {
+    pos: Vec2,
+    age: f32,
+}
+
+const STREAK_LIFETIME: f32 = 1.5;
+
+/// meters of zone area per streak
+const AREA_PER_STREAK: f32 = 400.0;
+
+impl WindZone {
+    pub fn rect(width: f32, height: f32, field: WindField) -> Self {
+        let (w, h) = (width / 2.0, height / 2.0);
+        Self::polygon(
+            vec![vec2(-w, -h), vec2(-w, h), vec2(w, h), vec2(w, -h)],
+            field,
+        )
+    }
+
+    pub fn polygon(area: Vec<Vec2>, field: WindField) -> Self {
+        debug_assert!(area.len() >= 3);
+        Self { area, field }
+    }
+
+    /// `local` is relative to the zone's position
+    pub fn contains(&self, local: Vec2) -> bool {
+        polygon_contains(&self.area, local)
+    }
+
+    /// velocity of the air at `local`, assumes `local` is inside the zone
+    pub fn air_velocity(&self, local: Vec2, time: f64) -> Vec2 {
+        match self.field {
+            WindField::Constant(v) => v,
+            WindField::Vortex { strength } => {
+                if local.length() < 0.001 {
+                    return vec2(0.0, 0.0);
+                }
+                vec2(-local.y, local.x).normalize() * strength
+            }
+            WindField::Turbulence {
+                strength,
+                scale,
+                speed,
+            } => {
+                let t = time as f32 * speed;
+                let angle = value_noise(0, local.x / scale + t, local.y / scale) * TAU * 2.0;
+                let magnitude = value_noise(1, local.x / scale, local.y / scale + t);
+                vec2(angle.cos(), angle.sin()) * magnitude * strength
+            }
+        }
+    }
+
+    fn area(&self) -> f32 {
+        let mut twice_area = 0.0;
+        for (a, b) in self.area.iter().zip(self.area.iter().cycle().skip(1)) {
+            twice_area += a.perp_dot(*b);
+        }
+        twice_area.abs() / 2.0
+    }
+
+    fn bounds(&self) -> (Vec2, Vec2) {
+        let mut min = self.area[0];
+        let mut max = self.area[0];
+        for p in &self.area {
+            min = min.min(*p);
+            max = max.max(*p);
+        }
+        (min, max)
+    }
+}
+
+/// All wind zones in the world, cloned so the world can be mutated while sampling wind.
+pub struct Winds(Vec<(Vec2, WindZone)>);
+
+impl Winds {
+    pub fn collect(w: &hecs::World) -> Self {
+        Self(
+            w.query::<(&Pos, &WindZone)>()
+                .iter()
+                .map(|(_, (p, z))| (p.0, z.clone()))
+                .collect(),
+        )
+    }
+
+    /// Sum of the air velocities of every zone containing `point`.
+    pub fn air_velocity(&self, point: Vec2, time: f64) -> Vec2 {
+        let mut ret = vec2(0.0, 0.0);
+        for (pos, zone) in &self.0 {
+            let local = point - *pos;
+            if zone.contains(local) {
+                ret += zone.air_velocity(local, time);
+            }
+        }
+        ret
+    }
+}
+
+impl WindZone {
+    /// Animates streaks. Bodies only feel the wind through drag, which is relative to the air
+    /// and so never pushes them past its speed, see `Heli::collision`.
+    pub fn system(w: &mut hecs::World) {
+        let Time { now: time, delta: delta_t } = Time::get(w);
+        let winds = Winds::collect(w);
+
+        for (_id, (pos, zone, streaks)) in w.query_mut::<(&Pos, &WindZone, &mut WindStreaks)>() {
+            let streaks = &mut streaks.0;
+            for streak in streaks.iter_mut() {
+                streak.pos += winds.air_velocity(pos.0 + streak.pos, time) * delta_t;
+                streak.age += delta_t;
+            }
+            streaks.retain(|s| s.age < STREAK_LIFETIME && zone.contains(s.pos));
+
+            let (min, max) = zone.bounds();
+            let target = (zone.area() / AREA_PER_STREAK).ceil() as usize;
+            // spawn at most a few per frame so streaks don't appear in lockstep
+            for _ in 0..4 {
+                if streaks.len() >= target {
+                    break;
+                }
+                let candidate = vec2(
+                    macroquad::rand::gen_range(min.x, max.x),
+                    macroquad::rand::gen_range(min.y, max.y),
+                );
+                if zone.contains(candidate) {
+                    streaks.push(Streak {
+                        pos: candidate,
+                        age: 0.0,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Expects the world camera to be set.
+    pub fn draw(w: &hecs::World) {
+        let meters_per_screen = 1.0 / WORLD_HEIGHT;
+        let time = Time::get(w).now;
+        let winds = Winds::collect(w);
+
+        for (_id, (pos, streaks)) in w.query::<(&Pos, &WindStreaks)>().iter() {
+            for streak in &streaks.0 {
+                let head = pos.0 + streak.pos;
+                let tail = head - winds.air_velocity(head, time) * 0.2;
+                // fade in and out
+                let life = streak.age / STREAK_LIFETIME;
+                let alpha = (life * (1.0 - life) * 4.0).min(1.0) * 0.5;
+                let (a, b) = (head * meters_per_screen, tail * meters_per_screen);
+                draw_line(
+                    a.x,
+                    a.y,
+                    b.x,
+                    b.y,
+                    meters_per_screen * 0.5,
+                    Color::new(1.0, 1.0, 1.0, alpha),
+                );
+            }
+        }
+    }
+}